@@ -1,114 +1,327 @@
+mod anchor;
+mod backend;
+mod charset;
 mod compatible;
+mod cookie_jar;
+mod dat_cache;
+mod next_thread;
+mod ng;
+mod post_date;
+mod post_error;
+mod read_range;
+mod setting;
 mod shitaraba;
+mod subject;
 
 use core::str;
+use std::{
+    fmt,
+    path::Path,
+    sync::{Arc, OnceLock},
+};
 
-use anyhow::{anyhow, Result};
-use encoding_rs::{Encoding, UTF_8};
+use anyhow::{anyhow, bail, Result};
+use encoding_rs::Encoding;
 use futures::StreamExt;
+use log::warn;
 use regex::Regex;
+use reqwest::header::HeaderMap;
 use url::Url;
 
-use self::compatible::Compatible;
-use self::shitaraba::Shitaraba;
+pub use self::anchor::{find_replies, ReplyTree};
+use self::backend::matching_backends;
+pub use self::charset::{lookup_encoding, unencodable_chars};
+use self::cookie_jar::CookieJar;
+use self::next_thread::pick_next_thread;
+pub use self::ng::{NgFilter, NgRule};
+pub use self::post_error::{PostError, PostErrorKind};
+pub use self::read_range::ReadRange;
+pub use self::setting::BoardSetting;
+pub use self::subject::SubjectEntry;
 
 pub const UA: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
 
+pub const DEFAULT_THREAD_STOP: u32 = 1000;
+
+static COOKIE_JAR: OnceLock<Arc<CookieJar>> = OnceLock::new();
+static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+fn cookie_jar() -> &'static Arc<CookieJar> {
+    COOKIE_JAR.get_or_init(Default::default)
+}
+
+/// Shared by every board so that cookies survive between requests.
+fn client() -> &'static reqwest::Client {
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .cookie_provider(cookie_jar().clone())
+            .build()
+            .unwrap()
+    })
+}
+
+/// Loads the persisted cookies from `data_dir` and enables the dat cache in `cache_dir`.
+pub fn init(data_dir: &Path, cache_dir: &Path) {
+    cookie_jar().load(data_dir.join("cookies.json"));
+    dat_cache::init(cache_dir);
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Post {
+    pub number: u32,
+    pub name: String,
+    pub mail: String,
+    pub date: String,
+    pub id: Option<String>,
+    pub body: String,
+    /// Post numbers referred to by `>>n` in the body.
+    pub anchors: Vec<u32>,
+}
+
+impl Post {
+    /// Milliseconds since the Unix epoch, if the date field can be read.
+    pub fn posted_at(&self) -> Option<u64> {
+        post_date::parse_post_date(&self.date)
+    }
+}
+
+/// The thread has fallen off the server (dat落ち).
+#[derive(Debug)]
+pub struct ThreadNotFound;
+
+impl fmt::Display for ThreadNotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Thread not found")
+    }
+}
+
+impl std::error::Error for ThreadNotFound {}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct ReadCursor {
+    pub last_number: u32,
+    pub title: Option<String>,
+    dat_size: usize,
+    last_modified: Option<String>,
+}
+
 #[async_trait::async_trait]
-pub trait Thread: Send + Sync {
+pub trait Board: Send + Sync {
+    fn board_url(&self) -> Url;
+
+    /// Returns the URL of a thread on this board.
+    fn thread_url(&self, key: u64) -> Url;
+
+    async fn fetch_subject(&self) -> Result<Vec<SubjectEntry>>;
+
+    async fn fetch_setting(&self) -> Result<BoardSetting>;
+
+    /// Assumed when neither the response nor its content names a charset.
+    fn encoding(&self) -> &'static Encoding;
+
+    /// Checks that a URL taken for a board really serves one.
+    async fn verify(&self) -> bool {
+        true
+    }
+
+    async fn fetch_latest_thread_url(&self) -> Result<Url> {
+        let entries = self.fetch_subject().await?;
+        let entry = entries
+            .first()
+            .ok_or_else(|| anyhow!("Empty subject.txt"))?;
+        Ok(self.thread_url(entry.key))
+    }
+}
+
+#[async_trait::async_trait]
+pub trait Thread: Board {
+    fn key(&self) -> u64;
+
+    /// The posts the URL asked for, e.g. `l50`.
+    fn read_range(&self) -> Option<ReadRange> {
+        None
+    }
+
+    /// Returns `None` when the thread has no posts after `cursor`.
+    async fn fetch_new_posts(
+        &self,
+        charset: &str,
+        cursor: &mut ReadCursor,
+    ) -> Result<Option<Vec<Post>>>;
+
+    /// Returns the posts kept in the dat cache and the cursor to resume from.
+    fn load_cached(&self, charset: &str) -> Option<(Vec<Post>, ReadCursor)>;
+
+    /// Reads the cache first and downloads only the rest. A thread that has
+    /// fallen off the server is served from the cache.
+    async fn fetch_posts(&self, charset: &str) -> Result<Vec<Post>> {
+        let (mut posts, mut cursor) = self.load_cached(charset).unwrap_or_default();
+        match self.fetch_new_posts(charset, &mut cursor).await {
+            Ok(new_posts) => merge_posts(&mut posts, new_posts.unwrap_or_default()),
+            Err(e) if !posts.is_empty() && e.is::<ThreadNotFound>() => {}
+            Err(e) => return Err(e),
+        }
+        Ok(posts)
+    }
+
     async fn post(&self, charset: &str, name: &str, email: &str, msg: &str) -> Result<()>;
 }
 
-pub async fn new(url: &Url) -> Result<Box<dyn Thread>> {
-    let host = url.host_str().ok_or_else(|| anyhow!("No host"))?;
-    let path = url.path();
-    if is_shitaraba_bbs(host, path) {
-        Ok(Box::new(Shitaraba::new(url).await?))
-    } else {
-        Ok(Box::new(Compatible::new(url).await?))
+pub fn new(url: &Url) -> Result<Box<dyn Thread>> {
+    matching_backends(url)
+        .find_map(|x| x.parse_thread_url(url))
+        .ok_or_else(|| anyhow!("Invalid URL: {}", url))
+}
+
+/// Appends `new_posts`, replacing the cached ones they overlap after a dat rewrite.
+fn merge_posts(posts: &mut Vec<Post>, new_posts: Vec<Post>) {
+    if let Some(first) = new_posts.first() {
+        posts.retain(|x| x.number < first.number);
     }
+    posts.extend(new_posts);
+}
+
+/// Decodes the character references boards and YPs escape text with.
+pub fn decode_entities(text: &str) -> String {
+    Regex::new(r"&(#[0-9]+|#[xX][0-9a-fA-F]+|[a-z]+);")
+        .unwrap()
+        .replace_all(text, |c: &regex::Captures| {
+            let entity = &c[1];
+            let ch = if let Some(hex) = entity.strip_prefix("#x").or(entity.strip_prefix("#X")) {
+                u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+            } else if let Some(dec) = entity.strip_prefix('#') {
+                dec.parse().ok().and_then(char::from_u32)
+            } else {
+                match entity {
+                    "amp" => Some('&'),
+                    "lt" => Some('<'),
+                    "gt" => Some('>'),
+                    "quot" => Some('"'),
+                    "apos" => Some('\''),
+                    "nbsp" => Some('\u{a0}'),
+                    _ => None,
+                }
+            };
+            ch.map(|x| x.to_string()).unwrap_or_else(|| c[0].to_owned())
+        })
+        .into_owned()
 }
 
-fn is_shitaraba_bbs(host: &str, path: &str) -> bool {
-    if host == "jbbs.shitaraba.net" {
-        return true;
+fn html_to_text(html: &str) -> String {
+    let text = Regex::new(r"(?i) ?<br> ?").unwrap().replace_all(html, "\n");
+    let text = Regex::new(r"<[^>]*>").unwrap().replace_all(&text, "");
+    decode_entities(&text)
+}
+
+fn split_date_id(date_id: &str) -> (String, Option<String>) {
+    match date_id.split_once(" ID:") {
+        Some((date, id)) => (date.to_owned(), Some(id.to_owned())),
+        None => (date_id.to_owned(), None),
     }
-    if path.starts_with("/bbs/read.cgi/") {
-        return true;
+}
+
+/// Checks `msg` against the board limits before sending it.
+async fn validate_message(
+    thread: &dyn Thread,
+    encoding: &'static Encoding,
+    msg: &str,
+) -> Result<()> {
+    match thread.fetch_setting().await {
+        Ok(setting) => setting.validate_message(encoding, msg)?,
+        Err(e) => warn!("Failed to fetch board setting: {}", e),
     }
-    false
+    Ok(())
+}
+
+/// Looks for the follow-up of `thread` titled `title` on its board.
+pub async fn find_next_thread(thread: &dyn Thread, title: &str) -> Result<Option<(Url, String)>> {
+    let entries = thread.fetch_subject().await?;
+    Ok(pick_next_thread(thread.key(), title, &entries)
+        .map(|entry| (thread.thread_url(entry.key), entry.title.clone())))
 }
 
 pub enum BbsUrl {
-    ProbablyShitarabaThread(Url, Box<dyn Thread>),
-    ProbablyShitarabaBoard(Url, String, u64),
-    ProbablyCompatibleThread(Url, Box<dyn Thread>),
-    MaybeCompatibleBoard(Url, String),
+    Thread(Url, Box<dyn Thread>),
+    Board(Url, Box<dyn Board>),
 }
 
 impl BbsUrl {
     pub fn into_url(self) -> Url {
         match self {
-            BbsUrl::ProbablyShitarabaThread(url, _) => url,
-            BbsUrl::ProbablyShitarabaBoard(url, _, _) => url,
-            BbsUrl::ProbablyCompatibleThread(url, _) => url,
-            BbsUrl::MaybeCompatibleBoard(url, _) => url,
+            BbsUrl::Thread(url, _) => url,
+            BbsUrl::Board(url, _) => url,
         }
     }
-}
 
-pub fn parse_bbs_url(url: Url) -> Result<BbsUrl, Url> {
-    if let Some(thread) = shitaraba::parse_thread_url(&url) {
-        return Ok(BbsUrl::ProbablyShitarabaThread(url, Box::new(thread)));
-    }
-    if let Some((dir, board)) = shitaraba::parse_board_url(&url) {
-        return Ok(BbsUrl::ProbablyShitarabaBoard(url, dir, board));
-    }
-    if let Some(thread) = compatible::parse_thread_url(&url) {
-        return Ok(BbsUrl::ProbablyCompatibleThread(url, Box::new(thread)));
+    pub fn read_range(&self) -> Option<ReadRange> {
+        match self {
+            BbsUrl::Thread(_, thread) => thread.read_range(),
+            BbsUrl::Board(_, _) => None,
+        }
     }
-    if let Some(board) = compatible::parse_board_url(&url) {
-        return Ok(BbsUrl::MaybeCompatibleBoard(url, board));
+
+    fn board(&self) -> &dyn Board {
+        match self {
+            BbsUrl::Thread(_, thread) => thread.as_ref(),
+            BbsUrl::Board(_, board) => board.as_ref(),
+        }
     }
-    Err(url)
 }
 
-async fn fetch_charset_title_pair(url: &Url) -> Result<(String, String)> {
-    let resp = reqwest::Client::new()
-        .get(url.clone())
+pub fn parse_bbs_url(url: Url) -> Result<BbsUrl, Url> {
+    let bbs_url = matching_backends(&url).find_map(|backend| {
+        if let Some(thread) = backend.parse_thread_url(&url) {
+            return Some(BbsUrl::Thread(url.clone(), thread));
+        }
+        Some(BbsUrl::Board(url.clone(), backend.parse_board_url(&url)?))
+    });
+    bbs_url.ok_or(url)
+}
+
+/// Reads the status, headers and at most about `limit` bytes of the body.
+async fn fetch_prefix(url: &str, limit: usize) -> Result<(HeaderMap, Vec<u8>)> {
+    let resp = client()
+        .get(url)
         .header("User-Agent", UA)
         .send()
         .await?
         .error_for_status()?;
+    let headers = resp.headers().clone();
     let mut bytes_stream = resp.bytes_stream();
     let mut buf = Vec::new();
     while let Some(chunk) = bytes_stream.next().await {
         let chunk = chunk?;
         buf.append(&mut chunk.to_vec());
-        if buf.len() > 1024 {
+        if buf.len() > limit {
             break;
         }
     }
-    let (charset, text) = 'block: {
-        let Some(charset) = regex::bytes::Regex::new(r#"charset=([^;"]+)|charset="(.+)""#)
-            .unwrap()
-            .captures(&buf)
-        else {
-            break 'block (UTF_8.name().to_owned(), String::from_utf8_lossy(&buf));
-        };
-        let charset = charset
-            .get(1)
-            .or_else(|| charset.get(2))
-            .unwrap()
-            .as_bytes();
-        let Some(encoding) = Encoding::for_label(charset) else {
-            break 'block (UTF_8.name().to_owned(), String::from_utf8_lossy(&buf));
-        };
-        let (text, _, _) = encoding.decode(&buf);
-        (String::from_utf8_lossy(charset).to_string(), text)
-    };
+    Ok((headers, buf))
+}
+
+/// GETs a text resource and decodes it, assuming `default` for the engine.
+pub async fn fetch_text(url: &str, default: &'static Encoding) -> Result<String> {
+    let resp = client()
+        .get(url)
+        .header("User-Agent", UA)
+        .send()
+        .await?
+        .error_for_status()?;
+    let headers = resp.headers().clone();
+    let bytes = resp.bytes().await?;
+    Ok(charset::decode(Some(&headers), &bytes, Some(default)))
+}
+
+async fn fetch_charset_title_pair(
+    url: &Url,
+    default: &'static Encoding,
+) -> Result<(String, String)> {
+    let (headers, buf) = fetch_prefix(url.as_str(), 4096).await?;
+    let encoding = charset::detect(Some(&headers), &buf, Some(default));
+    let (text, _, _) = encoding.decode(&buf);
     Ok((
-        charset,
+        encoding.name().to_owned(),
         Regex::new(r"(?i)<title>(.*?)</title>")
             .unwrap()
             .captures(text.as_ref())
@@ -118,26 +331,25 @@ async fn fetch_charset_title_pair(url: &Url) -> Result<(String, String)> {
 }
 
 pub async fn fetch_thread_url_encoding_name(bbs_url: &BbsUrl) -> Result<(Url, String, String)> {
-    match bbs_url {
-        BbsUrl::ProbablyShitarabaThread(url, _thread) => {
-            let (encoding, title) = fetch_charset_title_pair(url).await?;
-            Ok((url.clone(), encoding, title))
-        }
-        BbsUrl::ProbablyShitarabaBoard(url, dir, board) => {
-            let origin = url.origin().ascii_serialization();
-            let url = shitaraba::fetch_latest_thread_url(&origin, dir, *board).await?;
-            let (encoding, title) = fetch_charset_title_pair(&url).await?;
-            Ok((url, encoding, title))
-        }
-        BbsUrl::ProbablyCompatibleThread(url, _thread) => {
-            let (encoding, title) = fetch_charset_title_pair(url).await?;
-            Ok((url.clone(), encoding, title))
-        }
-        BbsUrl::MaybeCompatibleBoard(url, board) => {
-            let origin = url.origin().ascii_serialization();
-            let url = compatible::fetch_latest_thread_url(&origin, board).await?;
-            let (encoding, title) = fetch_charset_title_pair(&url).await?;
-            Ok((url, encoding, title))
+    let url = match bbs_url {
+        BbsUrl::Thread(_, thread) => thread.thread_url(thread.key()),
+        BbsUrl::Board(url, board) => {
+            if !board.verify().await {
+                bail!("Not a board: {}", url);
+            }
+            board.fetch_latest_thread_url().await?
         }
-    }
+    };
+    let (encoding, title) = fetch_charset_title_pair(&url, bbs_url.board().encoding()).await?;
+    Ok((url, encoding, title))
+}
+
+pub async fn fetch_thread_list(bbs_url: &BbsUrl) -> Result<Vec<(Url, SubjectEntry)>> {
+    let board = bbs_url.board();
+    Ok(board
+        .fetch_subject()
+        .await?
+        .into_iter()
+        .map(|entry| (board.thread_url(entry.key), entry))
+        .collect())
 }
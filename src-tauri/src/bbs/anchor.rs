@@ -0,0 +1,125 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use regex::Regex;
+
+use super::Post;
+
+/// Upper bound of numbers expanded from a single `>>n-m`.
+const MAX_RANGE_LEN: u32 = 100;
+
+fn parse_number(text: &str) -> Option<u32> {
+    text.chars()
+        .map(|c| match c {
+            '０'..='９' => char::from_u32(c as u32 - '０' as u32 + '0' as u32).unwrap(),
+            c => c,
+        })
+        .collect::<String>()
+        .parse()
+        .ok()
+}
+
+/// Parses `>>n`, `>>n-m` and `>>n,m` (and their full-width forms) in a post body.
+pub fn parse_anchors(body: &str) -> Vec<u32> {
+    let anchor = Regex::new(r"(?:>>|＞＞)([0-9０-９]+(?:[-－,，][0-9０-９]+)*)").unwrap();
+    let item = Regex::new(r"([0-9０-９]+)(?:[-－]([0-9０-９]+))?").unwrap();
+    let mut numbers = BTreeSet::new();
+    for c in anchor.captures_iter(body) {
+        for item in item.captures_iter(&c[1]) {
+            let Some(from) = parse_number(&item[1]) else {
+                continue;
+            };
+            let to = item
+                .get(2)
+                .and_then(|x| parse_number(x.as_str()))
+                .unwrap_or(from);
+            let (from, to) = (from.min(to), from.max(to));
+            numbers.extend((from..=to).take(MAX_RANGE_LEN as usize));
+        }
+    }
+    numbers.remove(&0);
+    numbers.into_iter().collect()
+}
+
+/// Reverse links of anchors in a thread.
+#[derive(Clone, Debug, Default)]
+pub struct ReplyTree {
+    replied_by: HashMap<u32, Vec<u32>>,
+}
+
+impl ReplyTree {
+    /// Returns the posts whose "replied by" list has changed.
+    pub fn add_posts(&mut self, posts: &[Post]) -> Vec<u32> {
+        let mut changed = BTreeSet::new();
+        for post in posts {
+            for &target in post.anchors.iter().filter(|&&x| x < post.number) {
+                let replied_by = self.replied_by.entry(target).or_default();
+                if !replied_by.contains(&post.number) {
+                    replied_by.push(post.number);
+                    changed.insert(target);
+                }
+            }
+        }
+        changed.into_iter().collect()
+    }
+
+    pub fn replied_by(&self, number: u32) -> &[u32] {
+        self.replied_by.get(&number).map_or(&[], |x| x.as_slice())
+    }
+}
+
+/// Picks the posts that anchor any of `own_numbers`.
+pub fn find_replies<'a>(posts: &'a [Post], own_numbers: &HashSet<u32>) -> Vec<&'a Post> {
+    posts
+        .iter()
+        .filter(|post| !own_numbers.contains(&post.number))
+        .filter(|post| post.anchors.iter().any(|x| own_numbers.contains(x)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post(number: u32, body: &str) -> Post {
+        Post {
+            number,
+            name: String::new(),
+            mail: String::new(),
+            date: String::new(),
+            id: None,
+            body: body.to_owned(),
+            anchors: parse_anchors(body),
+        }
+    }
+
+    #[test]
+    fn parses_anchors() {
+        assert_eq!(parse_anchors(">>1 ＞＞２ >>5-7"), vec![1, 2, 5, 6, 7]);
+        assert_eq!(parse_anchors(">>10,3 >>0"), vec![3, 10]);
+        assert_eq!(parse_anchors(">>1-100000").len(), MAX_RANGE_LEN as usize);
+        assert!(parse_anchors("> 1 >1").is_empty());
+    }
+
+    #[test]
+    fn tracks_replies() {
+        let posts = [
+            post(1, "a"),
+            post(2, ">>1"),
+            post(3, ">>1 >>2"),
+            post(4, ">>9"),
+        ];
+        let mut tree = ReplyTree::default();
+        assert_eq!(tree.add_posts(&posts), vec![1, 2]);
+        assert_eq!(tree.replied_by(1), [2, 3]);
+        assert_eq!(tree.replied_by(2), [3]);
+        assert!(tree.replied_by(4).is_empty());
+        assert!(tree.add_posts(&posts).is_empty());
+
+        let own = HashSet::from([1]);
+        let replies: Vec<_> = find_replies(&posts, &own)
+            .iter()
+            .map(|x| x.number)
+            .collect();
+        assert_eq!(replies, vec![2, 3]);
+    }
+}
@@ -0,0 +1,21 @@
+use url::Url;
+
+use super::{compatible::CompatibleBackend, shitaraba::ShitarabaBackend, Board, Thread};
+
+/// Describes a board engine. Adding an engine means implementing this in a
+/// new module and listing it in [`BACKENDS`].
+pub trait Backend: Sync {
+    /// Whether URLs on this host or path may belong to the engine.
+    fn matches(&self, url: &Url) -> bool;
+
+    fn parse_thread_url(&self, url: &Url) -> Option<Box<dyn Thread>>;
+
+    fn parse_board_url(&self, url: &Url) -> Option<Box<dyn Board>>;
+}
+
+/// Tried in order; the 2ch-compatible engine accepts any host and comes last.
+pub static BACKENDS: &[&dyn Backend] = &[&ShitarabaBackend, &CompatibleBackend];
+
+pub fn matching_backends(url: &Url) -> impl Iterator<Item = &'static dyn Backend> + '_ {
+    BACKENDS.iter().copied().filter(move |x| x.matches(url))
+}
@@ -0,0 +1,135 @@
+use anyhow::{anyhow, Result};
+use encoding_rs::{Encoding, EUC_JP, SHIFT_JIS, UTF_8};
+use percent_encoding::{percent_encode, NON_ALPHANUMERIC};
+use regex::{bytes, Regex};
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+
+/// Candidates for the statistical fallback.
+const GUESSES: &[&Encoding] = &[UTF_8, SHIFT_JIS, EUC_JP];
+
+fn from_content_type(headers: &HeaderMap) -> Option<&'static Encoding> {
+    let content_type = headers.get(CONTENT_TYPE)?.to_str().ok()?;
+    let c = Regex::new(r#"(?i)charset\s*=\s*"?([^;"\s]+)"#)
+        .unwrap()
+        .captures(content_type)?;
+    Encoding::for_label(c[1].as_bytes())
+}
+
+fn from_meta(body: &[u8]) -> Option<&'static Encoding> {
+    let c = bytes::Regex::new(r#"(?i-u)<meta\b[^>]*?charset\s*=\s*["']?([A-Za-z0-9_.:-]+)"#)
+        .unwrap()
+        .captures(body)?;
+    Encoding::for_label(&c[1])
+}
+
+/// Picks the candidate that decodes `body` with the fewest replacement
+/// characters, preferring `default` on a tie.
+fn guess(body: &[u8], default: Option<&'static Encoding>) -> &'static Encoding {
+    default
+        .into_iter()
+        .chain(GUESSES.iter().copied())
+        .min_by_key(|encoding| {
+            let (text, _) = encoding.decode_without_bom_handling(body);
+            text.matches('\u{fffd}').count()
+        })
+        .unwrap()
+}
+
+/// Detects the encoding of a response body from, in order, its BOM, the
+/// Content-Type header, a `<meta>` charset, UTF-8 validity, the engine's
+/// `default` if it decodes `body` cleanly, and finally a statistical guess.
+pub fn detect(
+    headers: Option<&HeaderMap>,
+    body: &[u8],
+    default: Option<&'static Encoding>,
+) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(body) {
+        return encoding;
+    }
+    if let Some(encoding) = headers.and_then(from_content_type) {
+        return encoding;
+    }
+    if let Some(encoding) = from_meta(body) {
+        return encoding;
+    }
+    if !body.is_ascii() && std::str::from_utf8(body).is_ok() {
+        return UTF_8;
+    }
+    match default {
+        Some(encoding)
+            if encoding
+                .decode_without_bom_handling_and_without_replacement(body)
+                .is_some() =>
+        {
+            encoding
+        }
+        _ => guess(body, default),
+    }
+}
+
+pub fn decode(
+    headers: Option<&HeaderMap>,
+    body: &[u8],
+    default: Option<&'static Encoding>,
+) -> String {
+    detect(headers, body, default).decode(body).0.into_owned()
+}
+
+/// Resolves a charset label received from outside, e.g. from the frontend.
+pub fn lookup_encoding(charset: &str) -> Result<&'static Encoding> {
+    Encoding::for_label(charset.as_bytes()).ok_or_else(|| anyhow!("Unknown charset: {}", charset))
+}
+
+/// Characters of `text` that `encoding` can only send as numeric character references.
+pub fn unencodable_chars(encoding: &'static Encoding, text: &str) -> Vec<char> {
+    let mut chars: Vec<_> = text
+        .chars()
+        .filter(|c| encoding.encode(c.encode_utf8(&mut [0; 4])).2)
+        .collect();
+    chars.sort_unstable();
+    chars.dedup();
+    chars
+}
+
+/// Characters missing from `encoding` become numeric character references
+/// such as `&#128512;`, which the boards display as the original character.
+pub fn percent_encode_in(encoding: &'static Encoding, text: &str) -> String {
+    let (text, _, _) = encoding.encode(text);
+    percent_encode(&text, NON_ALPHANUMERIC).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "今日はいい天気ですね。書き込みテスト";
+
+    #[test]
+    fn prefers_explicit_charsets() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, "text/plain; charset=EUC-JP".parse().unwrap());
+        assert_eq!(detect(Some(&headers), b"abc", Some(SHIFT_JIS)), EUC_JP);
+        assert_eq!(
+            detect(None, b"<meta charset=\"Shift_JIS\">", Some(UTF_8)),
+            SHIFT_JIS
+        );
+        assert_eq!(detect(None, TEXT.as_bytes(), Some(SHIFT_JIS)), UTF_8);
+    }
+
+    #[test]
+    fn guesses_when_the_default_does_not_fit() {
+        let (euc, _, _) = EUC_JP.encode(TEXT);
+        let (sjis, _, _) = SHIFT_JIS.encode(TEXT);
+        assert_eq!(detect(None, &euc, Some(SHIFT_JIS)), EUC_JP);
+        assert_eq!(detect(None, &sjis, Some(SHIFT_JIS)), SHIFT_JIS);
+        assert_eq!(detect(None, &sjis, Some(EUC_JP)), SHIFT_JIS);
+        assert_eq!(detect(None, &sjis, None), SHIFT_JIS);
+    }
+
+    #[test]
+    fn encodes_missing_characters_as_references() {
+        assert_eq!(unencodable_chars(SHIFT_JIS, "あ😀い😀"), vec!['😀']);
+        assert_eq!(percent_encode_in(SHIFT_JIS, "😀"), "%26%23128512%3B");
+        assert!(lookup_encoding("no-such-charset").is_err());
+    }
+}
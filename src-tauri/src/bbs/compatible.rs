@@ -1,102 +1,498 @@
-use anyhow::{anyhow, bail, Result};
-use encoding_rs::Encoding;
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use encoding_rs::{Encoding, SHIFT_JIS};
 use log::debug;
-use percent_encoding::{percent_encode, NON_ALPHANUMERIC};
 use regex::Regex;
-use reqwest::header::USER_AGENT;
+use reqwest::{
+    header::{
+        HeaderMap, CONTENT_TYPE, IF_MODIFIED_SINCE, LAST_MODIFIED, RANGE, REFERER, USER_AGENT,
+    },
+    StatusCode,
+};
 use url::Url;
 
-use super::{Thread, UA};
+use super::{
+    anchor::parse_anchors,
+    backend::Backend,
+    charset, client, dat_cache, decode_entities, fetch_prefix, fetch_text, html_to_text,
+    post_error::{check_post_response, PostErrorKind},
+    read_range::ReadRange,
+    setting::{cached_setting, BoardSetting},
+    split_date_id,
+    subject::{parse_subject_txt, SubjectEntry},
+    validate_message, Board, Post, ReadCursor, Thread, ThreadNotFound, UA,
+};
 
-pub fn parse_thread_url(thread_url: &Url) -> Option<Compatible> {
-    let origin = thread_url.origin().ascii_serialization();
-    let c = Regex::new(r"^/test/read.cgi/(.+?)/(.+?)(:?/.*)?$")
-        .unwrap()
-        .captures(thread_url.path())?;
-    let bbs = c.get(1).unwrap().as_str().to_string();
-    let key = c.get(2).unwrap().as_str().parse().ok()?;
-    Some(Compatible { origin, bbs, key })
+pub struct CompatibleBackend;
+
+impl Backend for CompatibleBackend {
+    /// Any host may run a 2ch-compatible engine.
+    fn matches(&self, _url: &Url) -> bool {
+        true
+    }
+
+    fn parse_thread_url(&self, url: &Url) -> Option<Box<dyn Thread>> {
+        Some(Box::new(parse_thread_url(url)?))
+    }
+
+    fn parse_board_url(&self, url: &Url) -> Option<Box<dyn Board>> {
+        Some(Box::new(parse_board_url(url)?))
+    }
+}
+
+/// Hosts serving `/<server>/test/read.cgi/...` for threads on `<server>.<domain>`.
+const MIRROR_HOSTS: &[(&str, &str)] = &[
+    ("itest.5ch.net", "5ch.net"),
+    ("itest.bbspink.com", "bbspink.com"),
+];
+
+fn parse_thread_url(thread_url: &Url) -> Option<Compatible> {
+    let mut origin = thread_url.origin().ascii_serialization();
+    let mut path = thread_url.path();
+    let host = thread_url.host_str()?;
+    if let Some((_, domain)) = MIRROR_HOSTS.iter().find(|(x, _)| *x == host) {
+        let (server, _) = path.strip_prefix('/')?.split_once('/')?;
+        origin = format!("{}://{}.{}", thread_url.scheme(), server, domain);
+        path = &path[server.len() + 1..];
+    }
+    let (bbs, key, range) = if path == "/test/read.cgi" {
+        // Old style: read.cgi?bbs=...&key=...&ls=50
+        let query = |name: &str| {
+            thread_url
+                .query_pairs()
+                .find(|(x, _)| x == name)
+                .map(|(_, x)| x.into_owned())
+        };
+        let key = query("key")?.parse().ok()?;
+        (query("bbs")?, key, ReadRange::from_query(thread_url))
+    } else {
+        let c = Regex::new(r"^/test/read\.cgi/([^/]+)/([0-9]+)(?:/([^/]*))?")
+            .unwrap()
+            .captures(path)?;
+        let range = c.get(3).and_then(|x| ReadRange::parse(x.as_str()));
+        (c[1].to_owned(), c[2].parse().ok()?, range)
+    };
+    Some(Compatible {
+        board: CompatibleBoard { origin, bbs },
+        key,
+        range,
+    })
 }
 
-pub fn parse_board_url(board_url: &Url) -> Option<String> {
-    let c = Regex::new(r"^/(.+?)/?$")
+fn parse_board_url(board_url: &Url) -> Option<CompatibleBoard> {
+    let origin = board_url.origin().ascii_serialization();
+    let c = Regex::new(r"^/([^/]+)/?$")
         .unwrap()
         .captures(board_url.path())?;
-    Some(c.get(1).unwrap().as_str().to_string())
+    let bbs = c.get(1).unwrap().as_str().to_string();
+    Some(CompatibleBoard { origin, bbs })
 }
 
 async fn fetch_subject_txt(origin: &str, bbs: &str) -> Result<String> {
-    let subject_url = format!("{}/{}/subject.txt", origin, bbs);
-    Ok(reqwest::get(subject_url).await?.text().await?)
+    fetch_text(&format!("{}/{}/subject.txt", origin, bbs), SHIFT_JIS).await
 }
 
-fn read_latest_thread(subject_txt: &str) -> Result<u64> {
-    Ok(subject_txt
-        .lines()
-        .next()
-        .ok_or_else(|| anyhow!("Empty subject.txt"))?
-        .split(".")
-        .next()
-        .ok_or_else(|| anyhow!("Empty subject.txt"))?
-        .parse()?)
+fn thread_url(origin: &str, bbs: &str, key: u64) -> Url {
+    let thread_url = format!("{}/test/read.cgi/{}/{}", origin, bbs, key);
+    Url::parse(&thread_url).unwrap()
 }
 
-async fn fetch_latest_thread(origin: &str, bbs: &str) -> Result<u64> {
-    let subject_txt = fetch_subject_txt(origin, bbs).await?;
-    read_latest_thread(&subject_txt)
+/// Whether the origin has been found to host boards, so that other URLs on
+/// the same host skip the probe.
+static BOARD_ORIGINS: OnceLock<Mutex<HashMap<String, bool>>> = OnceLock::new();
+
+fn is_plain_text(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|x| x.to_str().ok())
+        .is_none_or(|x| x.starts_with("text/plain"))
 }
 
-pub async fn fetch_latest_thread_url(origin: &str, bbs: &str) -> Result<Url> {
-    let key = fetch_latest_thread(origin, bbs).await?;
-    let thread_url = format!("{}/test/read.cgi/{}/{}", origin, bbs, key);
-    Ok(Url::parse(&thread_url).unwrap())
+/// Whether the server answered that the resource doesn't exist, as opposed to
+/// being unreachable.
+fn is_client_error(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<reqwest::Error>()
+        .and_then(|x| x.status())
+        .is_some_and(|x| x.is_client_error())
 }
 
-fn charset_percent_encode(encoding: &'static Encoding, text: &str) -> String {
-    let (text, _, _) = encoding.encode(text);
-    percent_encode(&text, NON_ALPHANUMERIC).to_string()
+/// Looks for `BBS_*` keys in SETTING.TXT, then for a valid first line in
+/// subject.txt. `None` when the server couldn't be asked, e.g. offline.
+async fn probe_board(origin: &str, bbs: &str) -> Option<bool> {
+    let mut answered = false;
+    let url = format!("{}/{}/SETTING.TXT", origin, bbs);
+    match fetch_prefix(&url, 4096).await {
+        Ok((headers, bytes)) => {
+            let text = charset::decode(Some(&headers), &bytes, Some(SHIFT_JIS));
+            if is_plain_text(&headers) && text.lines().any(|x| x.starts_with("BBS_")) {
+                return Some(true);
+            }
+            answered = true;
+        }
+        Err(e) => answered |= is_client_error(&e),
+    }
+    let url = format!("{}/{}/subject.txt", origin, bbs);
+    match fetch_prefix(&url, 4096).await {
+        Ok((headers, bytes)) => {
+            let text = charset::decode(Some(&headers), &bytes, Some(SHIFT_JIS));
+            let first_line = text.lines().next().unwrap_or_default();
+            if is_plain_text(&headers) && parse_subject_txt(first_line).len() == 1 {
+                return Some(true);
+            }
+            answered = true;
+        }
+        Err(e) => answered |= is_client_error(&e),
+    }
+    answered.then_some(false)
 }
 
-pub struct Compatible {
+async fn fetch_setting_txt(origin: &str, bbs: &str) -> Result<String> {
+    fetch_text(&format!("{}/{}/SETTING.TXT", origin, bbs), SHIFT_JIS).await
+}
+
+fn parse_dat(dat: &str, first_number: u32) -> Vec<Post> {
+    dat.lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let mut fields = line.split("<>");
+            let name = fields.next().unwrap_or_default();
+            let mail = fields.next().unwrap_or_default();
+            let (date, id) = split_date_id(fields.next().unwrap_or_default());
+            let body = html_to_text(fields.next().unwrap_or_default().trim());
+            Post {
+                number: first_number + i as u32,
+                name: html_to_text(name),
+                mail: html_to_text(mail),
+                date,
+                id,
+                anchors: parse_anchors(&body),
+                body,
+            }
+        })
+        .collect()
+}
+
+fn parse_dat_title(dat: &str) -> Option<String> {
+    let title = dat.lines().next()?.split("<>").nth(4)?;
+    Some(html_to_text(title.trim())).filter(|x| !x.is_empty())
+}
+
+/// Collects `<input type="hidden">` and `<input type="submit">` fields of a form.
+fn parse_hidden_fields(html: &str) -> Vec<(String, String)> {
+    let attr = |tag: &str, name: &str| {
+        Regex::new(&format!(
+            r#"(?i)\b{}\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s>]*))"#,
+            name
+        ))
+        .unwrap()
+        .captures(tag)
+        .and_then(|c| c.get(1).or(c.get(2)).or(c.get(3)))
+        .map(|x| decode_entities(x.as_str()))
+    };
+    Regex::new(r"(?i)<input\b[^>]*>")
+        .unwrap()
+        .find_iter(html)
+        .filter_map(|tag| {
+            let tag = tag.as_str();
+            let ty = attr(tag, "type")?.to_ascii_lowercase();
+            if ty != "hidden" && ty != "submit" {
+                return None;
+            }
+            Some((attr(tag, "name")?, attr(tag, "value").unwrap_or_default()))
+        })
+        .collect()
+}
+
+pub struct CompatibleBoard {
     origin: String,
     bbs: String,
-    key: u64,
 }
 
-impl Compatible {
-    pub async fn new(url: &Url) -> Result<Self> {
-        if let Some(site) = parse_thread_url(url) {
-            return Ok(site);
+#[async_trait::async_trait]
+impl Board for CompatibleBoard {
+    fn board_url(&self) -> Url {
+        Url::parse(&format!("{}/{}/", self.origin, self.bbs)).unwrap()
+    }
+
+    fn thread_url(&self, key: u64) -> Url {
+        thread_url(&self.origin, &self.bbs, key)
+    }
+
+    async fn fetch_subject(&self) -> Result<Vec<SubjectEntry>> {
+        let subject_txt = fetch_subject_txt(&self.origin, &self.bbs).await?;
+        Ok(parse_subject_txt(&subject_txt))
+    }
+
+    async fn fetch_setting(&self) -> Result<BoardSetting> {
+        cached_setting(
+            self.board_url().to_string(),
+            fetch_setting_txt(&self.origin, &self.bbs),
+        )
+        .await
+    }
+
+    fn encoding(&self) -> &'static Encoding {
+        SHIFT_JIS
+    }
+
+    async fn verify(&self) -> bool {
+        let origins = BOARD_ORIGINS.get_or_init(Default::default);
+        if let Some(verdict) = origins.lock().unwrap().get(&self.origin) {
+            return *verdict;
         }
-        bail!("Invalid URL: {}", url);
+        let Some(verdict) = probe_board(&self.origin, &self.bbs).await else {
+            // Probe again next time and let the fetch report the network error.
+            return true;
+        };
+        debug!("{} hosts boards: {}", self.origin, verdict);
+        origins.lock().unwrap().insert(self.origin.clone(), verdict);
+        verdict
     }
 }
 
-#[async_trait::async_trait]
-impl Thread for Compatible {
-    async fn post(&self, charset: &str, name: &str, email: &str, msg: &str) -> Result<()> {
-        let encoding = Encoding::for_label(charset.as_bytes()).unwrap();
-        let name = charset_percent_encode(encoding, name);
-        let email = charset_percent_encode(encoding, email);
-        let msg = charset_percent_encode(encoding, msg);
-        let resp = reqwest::Client::new()
-            .post(format!("{}/test/bbs.cgi", self.origin))
+pub struct Compatible {
+    board: CompatibleBoard,
+    key: u64,
+    range: Option<ReadRange>,
+}
+
+impl Compatible {
+    async fn submit(
+        &self,
+        encoding: &'static Encoding,
+        charset: &str,
+        fields: &[(String, String)],
+    ) -> Result<String> {
+        let body = fields
+            .iter()
+            .map(|(name, value)| {
+                format!("{}={}", name, charset::percent_encode_in(encoding, value))
+            })
+            .collect::<Vec<_>>()
+            .join("&");
+        let resp = client()
+            .post(format!("{}/test/bbs.cgi", self.board.origin))
             .header(USER_AGENT, UA)
             .header(
                 "Content-Type",
                 format!("application/x-www-form-urlencoded; charset={}", charset),
             )
-            .header("Cookie", format!(r#"NAME="{}";MAIL="{}""#, name, email))
-            .body(format!(
-                "FROM={}&mail={}&MESSAGE={}&key={}&bbs={}",
-                name, email, msg, self.key, self.bbs
-            ))
+            .header(
+                REFERER,
+                thread_url(&self.board.origin, &self.board.bbs, self.key).as_str(),
+            )
+            .body(body)
             .send()
             .await?
             .error_for_status()?;
+        let headers = resp.headers().clone();
         let bytes = resp.bytes().await?;
-        let text = Encoding::for_label(b"shift_jis").unwrap().decode(&bytes).0;
-        debug!("post resp: {}", text.to_string());
+        let text = charset::decode(Some(&headers), &bytes, Some(encoding));
+        debug!("post resp: {}", text);
+        Ok(text)
+    }
+}
+
+#[async_trait::async_trait]
+impl Board for Compatible {
+    fn board_url(&self) -> Url {
+        self.board.board_url()
+    }
+
+    fn thread_url(&self, key: u64) -> Url {
+        self.board.thread_url(key)
+    }
+
+    async fn fetch_subject(&self) -> Result<Vec<SubjectEntry>> {
+        self.board.fetch_subject().await
+    }
+
+    async fn fetch_setting(&self) -> Result<BoardSetting> {
+        self.board.fetch_setting().await
+    }
+
+    fn encoding(&self) -> &'static Encoding {
+        self.board.encoding()
+    }
+}
+
+#[async_trait::async_trait]
+impl Thread for Compatible {
+    fn key(&self) -> u64 {
+        self.key
+    }
+
+    fn read_range(&self) -> Option<ReadRange> {
+        self.range
+    }
+
+    fn load_cached(&self, charset: &str) -> Option<(Vec<Post>, ReadCursor)> {
+        let encoding = charset::lookup_encoding(charset).ok()?;
+        let (dat, cursor) = dat_cache::load(&self.board_url(), self.key)?;
+        Some((
+            parse_dat(&charset::decode(None, &dat, Some(encoding)), 1),
+            cursor,
+        ))
+    }
+
+    async fn fetch_new_posts(
+        &self,
+        charset: &str,
+        cursor: &mut ReadCursor,
+    ) -> Result<Option<Vec<Post>>> {
+        let encoding = charset::lookup_encoding(charset)?;
+        let mut req = client()
+            .get(format!(
+                "{}/{}/dat/{}.dat",
+                self.board.origin, self.board.bbs, self.key
+            ))
+            .header(USER_AGENT, UA);
+        if cursor.dat_size > 0 {
+            // Request one byte before the end to detect a rewritten dat.
+            req = req.header(RANGE, format!("bytes={}-", cursor.dat_size - 1));
+        }
+        if let Some(last_modified) = &cursor.last_modified {
+            req = req.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        let resp = req.send().await?;
+        if resp.status() == StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+        if resp.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            // The range starts inside the known dat, so it has shrunk or been rewritten.
+            *cursor = ReadCursor::default();
+            return self.fetch_new_posts(charset, cursor).await;
+        }
+        if matches!(resp.status(), StatusCode::NOT_FOUND | StatusCode::GONE) {
+            return Err(ThreadNotFound.into());
+        }
+        let resp = resp.error_for_status()?;
+        // Some servers redirect a dropped thread to an HTML page.
+        if resp
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|x| x.to_str().ok())
+            .is_some_and(|x| x.starts_with("text/html"))
+        {
+            return Err(ThreadNotFound.into());
+        }
+        let partial = resp.status() == StatusCode::PARTIAL_CONTENT;
+        let last_modified = resp
+            .headers()
+            .get(LAST_MODIFIED)
+            .and_then(|x| x.to_str().ok())
+            .map(|x| x.to_owned());
+        let headers = resp.headers().clone();
+        let bytes = resp.bytes().await?;
+        let bytes = if partial {
+            if bytes.first() != Some(&b'\n') {
+                *cursor = ReadCursor::default();
+                return self.fetch_new_posts(charset, cursor).await;
+            }
+            &bytes[1..]
+        } else {
+            cursor.dat_size = 0;
+            &bytes[..]
+        };
+        // The last line may still be being written.
+        let len = bytes.iter().rposition(|&x| x == b'\n').map_or(0, |x| x + 1);
+        cursor.dat_size += len;
+        cursor.last_modified = last_modified;
+        let dat = charset::decode(Some(&headers), &bytes[..len], Some(encoding));
+        let first_number = if partial { cursor.last_number + 1 } else { 1 };
+        if first_number == 1 {
+            cursor.title = parse_dat_title(&dat);
+        }
+        let posts: Vec<_> = parse_dat(&dat, first_number)
+            .into_iter()
+            .filter(|post| post.number > cursor.last_number)
+            .collect();
+        if let Some(last) = posts.last() {
+            cursor.last_number = last.number;
+        }
+        if !partial || len > 0 {
+            dat_cache::store(&self.board_url(), self.key, &bytes[..len], partial, cursor);
+        }
+        Ok(Some(posts).filter(|x| !x.is_empty()))
+    }
+
+    async fn post(&self, charset: &str, name: &str, email: &str, msg: &str) -> Result<()> {
+        let encoding = charset::lookup_encoding(charset)?;
+        validate_message(self, encoding, msg).await?;
+        let time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let mut fields = vec![
+            ("FROM".to_owned(), name.to_owned()),
+            ("mail".to_owned(), email.to_owned()),
+            ("MESSAGE".to_owned(), msg.to_owned()),
+            ("bbs".to_owned(), self.board.bbs.clone()),
+            ("key".to_owned(), self.key.to_string()),
+            ("time".to_owned(), time.to_string()),
+        ];
+        let text = self.submit(encoding, charset, &fields).await?;
+        match check_post_response(&text) {
+            Err(err) if err.kind == PostErrorKind::ConfirmationRequired => {
+                // The confirmation page has set cookies; send the form again.
+                for (name, value) in parse_hidden_fields(&text) {
+                    if !fields.iter().any(|(x, _)| *x == name) {
+                        fields.push((name, value));
+                    }
+                }
+                let text = self.submit(encoding, charset, &fields).await?;
+                check_post_response(&text)?;
+            }
+            result => result?,
+        }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_thread_urls() {
+        let url = Url::parse("https://itest.5ch.net/egg/test/read.cgi/livejupiter/1729222496/l50")
+            .unwrap();
+        let thread = parse_thread_url(&url).unwrap();
+        assert_eq!(thread.board.origin, "https://egg.5ch.net");
+        assert_eq!(thread.board.bbs, "livejupiter");
+        assert_eq!(thread.key, 1729222496);
+        assert_eq!(thread.range, ReadRange::parse("l50"));
+
+        let url = Url::parse("http://example.com/test/read.cgi?bbs=test&key=123").unwrap();
+        let thread = parse_thread_url(&url).unwrap();
+        assert_eq!(thread.board.origin, "http://example.com");
+        assert_eq!(thread.board.bbs, "test");
+        assert_eq!(thread.key, 123);
+        assert_eq!(thread.range, None);
+
+        let url = Url::parse("http://example.com/test/read.cgi/test/").unwrap();
+        assert!(parse_thread_url(&url).is_none());
+    }
+
+    #[test]
+    fn parses_dat() {
+        let dat = concat!(
+            "<b>名無し</b><>sage<>2024/10/18(金) 12:34:56.78 ID:Abc123<> 本文 <br> &gt;&gt;1 <>スレタイ &amp; テスト\n",
+            "名無し<><>2024/10/18(金) 12:35:00<> <a href=\"../test/read.cgi/test/1/1\">&gt;&gt;1</a> <>\n",
+        );
+        let posts = parse_dat(dat, 10);
+        assert_eq!(posts.len(), 2);
+        assert_eq!(posts[0].number, 10);
+        assert_eq!(posts[0].name, "名無し");
+        assert_eq!(posts[0].mail, "sage");
+        assert_eq!(posts[0].date, "2024/10/18(金) 12:34:56.78");
+        assert_eq!(posts[0].id.as_deref(), Some("Abc123"));
+        assert_eq!(posts[0].body, "本文\n>>1");
+        assert_eq!(posts[1].number, 11);
+        assert_eq!(posts[1].id, None);
+        assert_eq!(posts[1].anchors, vec![1]);
+        assert_eq!(parse_dat_title(dat).as_deref(), Some("スレタイ & テスト"));
+    }
+}
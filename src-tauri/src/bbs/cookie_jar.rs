@@ -0,0 +1,302 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::warn;
+use regex::Regex;
+use reqwest::{cookie::CookieStore, header::HeaderValue};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use super::post_date::days_from_civil;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Cookie {
+    value: String,
+    /// Seconds since the Unix epoch.
+    expires: i64,
+}
+
+type Cookies<T> = HashMap<String, BTreeMap<String, T>>;
+
+/// Cookies keyed by origin. Those with an expiry are saved as JSON after
+/// every change; session cookies are only kept in memory.
+#[derive(Default)]
+pub struct CookieJar {
+    path: Mutex<Option<PathBuf>>,
+    cookies: Mutex<Cookies<Cookie>>,
+    session_cookies: Mutex<Cookies<String>>,
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |x| x.as_secs() as i64)
+}
+
+fn remove_expired(cookies: &mut Cookies<Cookie>, now: i64) -> bool {
+    let mut removed = false;
+    for entry in cookies.values_mut() {
+        let len = entry.len();
+        entry.retain(|_, x| x.expires > now);
+        removed |= entry.len() != len;
+    }
+    removed
+}
+
+fn save(path: &Path, cookies: &Cookies<Cookie>) {
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(path, serde_json::to_vec(cookies).unwrap()));
+    if let Err(e) = result {
+        warn!("Failed to save cookies: {}", e);
+    }
+}
+
+impl CookieJar {
+    pub fn load(&self, path: PathBuf) {
+        match fs::read(&path) {
+            Ok(json) => match serde_json::from_slice::<Cookies<Cookie>>(&json) {
+                Ok(mut cookies) => {
+                    remove_expired(&mut cookies, now());
+                    *self.cookies.lock().unwrap() = cookies;
+                }
+                Err(e) => warn!("Failed to parse cookies: {}", e),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Failed to read cookies: {}", e),
+        }
+        *self.path.lock().unwrap() = Some(path);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Lifetime {
+    Deleted,
+    Session,
+    /// Expires at the given seconds since the Unix epoch.
+    Persistent(i64),
+}
+
+/// Parses an HTTP date such as `Thu, 01-Jan-1970 00:00:00 GMT` into seconds
+/// since the Unix epoch.
+fn parse_expires(text: &str) -> Option<i64> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+    let c = Regex::new(
+        r"([0-9]{1,2})[ -]([A-Za-z]{3})[ -]([0-9]{2,4}) ([0-9]{1,2}):([0-9]{2}):([0-9]{2})",
+    )
+    .unwrap()
+    .captures(text)?;
+    let number = |i: usize| c[i].parse::<i64>().ok();
+    let month = MONTHS.iter().position(|x| c[2].eq_ignore_ascii_case(x))? as i64 + 1;
+    let year = match number(3)? {
+        x @ 0..=69 => x + 2000,
+        x @ 70..=99 => x + 1900,
+        x => x,
+    };
+    Some(
+        days_from_civil(year, month, number(1)?) * 86400
+            + number(4)? * 3600
+            + number(5)? * 60
+            + number(6)?,
+    )
+}
+
+/// Returns the name, value and lifetime of the cookie. `Max-Age` wins over
+/// `Expires`, and an empty value or a time in the past deletes the cookie.
+fn parse_set_cookie(set_cookie: &str, now: i64) -> Option<(String, String, Lifetime)> {
+    let mut attrs = set_cookie.split(';').map(|x| x.trim());
+    let (name, value) = attrs.next()?.split_once('=')?;
+    let value = value.trim_matches('"');
+    let mut max_age = None;
+    let mut expires = None;
+    for (key, attr_value) in attrs.filter_map(|x| x.split_once('=')) {
+        if key.eq_ignore_ascii_case("max-age") {
+            max_age = attr_value.trim().parse::<i64>().ok();
+        } else if key.eq_ignore_ascii_case("expires") {
+            expires = parse_expires(attr_value);
+        }
+    }
+    let lifetime = match (max_age, expires) {
+        _ if value.is_empty() => Lifetime::Deleted,
+        (Some(x), _) if x <= 0 => Lifetime::Deleted,
+        (Some(x), _) => Lifetime::Persistent(now.saturating_add(x)),
+        (None, Some(x)) if x <= now => Lifetime::Deleted,
+        (None, Some(x)) => Lifetime::Persistent(x),
+        (None, None) => Lifetime::Session,
+    };
+    Some((name.trim().to_owned(), value.to_owned(), lifetime))
+}
+
+impl CookieStore for CookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        let origin = url.origin().ascii_serialization();
+        let now = now();
+        let mut cookies = self.cookies.lock().unwrap();
+        let mut session_cookies = self.session_cookies.lock().unwrap();
+        let entry = cookies.entry(origin.clone()).or_default();
+        let session_entry = session_cookies.entry(origin).or_default();
+        let mut changed = false;
+        for (name, value, lifetime) in cookie_headers
+            .filter_map(|x| x.to_str().ok())
+            .filter_map(|x| parse_set_cookie(x, now))
+        {
+            changed |= match lifetime {
+                Lifetime::Deleted => {
+                    session_entry.remove(&name);
+                    entry.remove(&name).is_some()
+                }
+                Lifetime::Session => {
+                    session_entry.insert(name.clone(), value);
+                    entry.remove(&name).is_some()
+                }
+                Lifetime::Persistent(expires) => {
+                    session_entry.remove(&name);
+                    let cookie = Cookie { value, expires };
+                    entry.insert(name, cookie.clone()) != Some(cookie)
+                }
+            };
+        }
+        changed |= remove_expired(&mut cookies, now);
+        if !changed {
+            return;
+        }
+        // Taking the path lock before releasing the cookies keeps concurrent
+        // saves in order without writing the file under the cookie locks.
+        let path = self.path.lock().unwrap();
+        let snapshot = cookies.clone();
+        drop(session_cookies);
+        drop(cookies);
+        if let Some(path) = path.as_deref() {
+            save(path, &snapshot);
+        }
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        let origin = url.origin().ascii_serialization();
+        let mut cookies = self.cookies.lock().unwrap();
+        // The file drops them on the next change or load.
+        remove_expired(&mut cookies, now());
+        let session_cookies = self.session_cookies.lock().unwrap();
+        let header = cookies
+            .get(&origin)
+            .into_iter()
+            .flatten()
+            .map(|(name, x)| (name, &x.value))
+            .chain(session_cookies.get(&origin).into_iter().flatten())
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join("; ");
+        if header.is_empty() {
+            return None;
+        }
+        HeaderValue::from_str(&header).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1729222496;
+
+    fn lifetime(set_cookie: &str) -> Lifetime {
+        parse_set_cookie(set_cookie, NOW).unwrap().2
+    }
+
+    #[test]
+    fn parses_lifetimes() {
+        assert_eq!(lifetime("a=b; path=/"), Lifetime::Session);
+        assert_eq!(lifetime("a=b; Max-Age=60"), Lifetime::Persistent(NOW + 60));
+        assert_eq!(lifetime("a=b; Max-Age=0"), Lifetime::Deleted);
+        assert_eq!(lifetime("a=; path=/"), Lifetime::Deleted);
+        assert_eq!(
+            lifetime("a=b; expires=Thu, 01-Jan-1970 00:00:01 GMT"),
+            Lifetime::Deleted
+        );
+        assert_eq!(
+            lifetime("a=b; Expires=Wed, 21 Oct 2099 07:28:00 GMT"),
+            Lifetime::Persistent(4096250880)
+        );
+        assert_eq!(
+            lifetime("a=b; Expires=Thu, 01-Jan-70 00:00:01 GMT; Max-Age=60"),
+            Lifetime::Persistent(NOW + 60)
+        );
+        assert_eq!(
+            parse_expires("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(1445412480)
+        );
+    }
+
+    #[test]
+    fn keeps_session_cookies_out_of_the_file() {
+        let jar = CookieJar::default();
+        let url = Url::parse("https://example.com/test/bbs.cgi").unwrap();
+        let headers = [
+            HeaderValue::from_static("session=1; path=/"),
+            HeaderValue::from_static("persistent=2; Max-Age=3600"),
+        ];
+        jar.set_cookies(&mut headers.iter(), &url);
+        assert_eq!(
+            jar.cookies(&url).unwrap(),
+            HeaderValue::from_static("persistent=2; session=1")
+        );
+        assert_eq!(jar.cookies.lock().unwrap()["https://example.com"].len(), 1);
+
+        let headers = [HeaderValue::from_static(
+            "persistent=2; Expires=Thu, 01-Jan-1970 00:00:01 GMT",
+        )];
+        jar.set_cookies(&mut headers.iter(), &url);
+        assert_eq!(
+            jar.cookies(&url).unwrap(),
+            HeaderValue::from_static("session=1")
+        );
+    }
+
+    #[test]
+    fn drops_expired_cookies() {
+        let dir = std::env::temp_dir().join(format!("cookie-jar-test-{}", std::process::id()));
+        let path = dir.join("cookies.json");
+        let cookie = |value: &str, expires| Cookie {
+            value: value.to_owned(),
+            expires,
+        };
+        let saved: Cookies<Cookie> = HashMap::from([(
+            "https://example.com".to_owned(),
+            BTreeMap::from([
+                ("expired".to_owned(), cookie("1", NOW)),
+                ("valid".to_owned(), cookie("2", i64::MAX)),
+            ]),
+        )]);
+        save(&path, &saved);
+
+        let jar = CookieJar::default();
+        jar.load(path.clone());
+        let url = Url::parse("https://example.com/").unwrap();
+        assert_eq!(
+            jar.cookies(&url).unwrap(),
+            HeaderValue::from_static("valid=2")
+        );
+
+        // Expired while the app was running.
+        jar.cookies
+            .lock()
+            .unwrap()
+            .get_mut("https://example.com")
+            .unwrap()
+            .insert("stale".to_owned(), cookie("3", NOW));
+        assert_eq!(
+            jar.cookies(&url).unwrap(),
+            HeaderValue::from_static("valid=2")
+        );
+        assert_eq!(jar.cookies.lock().unwrap()["https://example.com"].len(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
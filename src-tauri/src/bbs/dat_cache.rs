@@ -0,0 +1,174 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::SystemTime,
+};
+
+use log::{debug, warn};
+use url::Url;
+
+use super::ReadCursor;
+
+const MAX_CACHE_BYTES: u64 = 256 * 1024 * 1024;
+
+static CACHE_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Enables the cache under `cache_dir` and trims it to the size limit.
+pub fn init(cache_dir: &Path) {
+    let dir = cache_dir.join("dat");
+    if CACHE_DIR.set(dir.clone()).is_err() {
+        return;
+    }
+    evict(&dir, MAX_CACHE_BYTES);
+}
+
+/// `<cache>/dat/<host>/<board path>/<key>.dat`, next to `<key>.json` holding the cursor.
+fn dat_path(board_url: &Url, key: u64) -> Option<PathBuf> {
+    let mut path = CACHE_DIR.get()?.join(board_url.host_str()?);
+    for segment in board_url.path_segments()?.filter(|x| !x.is_empty()) {
+        path.push(segment);
+    }
+    path.push(format!("{}.dat", key));
+    Some(path)
+}
+
+pub fn load(board_url: &Url, key: u64) -> Option<(Vec<u8>, ReadCursor)> {
+    let path = dat_path(board_url, key)?;
+    let dat = fs::read(&path).ok()?;
+    let cursor = fs::read(path.with_extension("json"))
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())?;
+    Some((dat, cursor))
+}
+
+fn write(path: &Path, dat: &[u8], append: bool, cursor: &ReadCursor) -> io::Result<bool> {
+    let is_new = !path.exists();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)?;
+    file.write_all(dat)?;
+    fs::write(path.with_extension("json"), serde_json::to_vec(cursor)?)?;
+    Ok(is_new)
+}
+
+/// Appends newly fetched raw data, or replaces the whole file unless `append`.
+pub fn store(board_url: &Url, key: u64, dat: &[u8], append: bool, cursor: &ReadCursor) {
+    let Some(path) = dat_path(board_url, key) else {
+        return;
+    };
+    match write(&path, dat, append, cursor) {
+        Ok(true) => evict(CACHE_DIR.get().unwrap(), MAX_CACHE_BYTES),
+        Ok(false) => {}
+        Err(e) => warn!("Failed to write dat cache: {}", e),
+    }
+}
+
+fn collect_dat_files(dir: &Path, files: &mut Vec<(PathBuf, u64, SystemTime)>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let path = entry.path();
+        if metadata.is_dir() {
+            collect_dat_files(&path, files)?;
+        } else if path.extension().is_some_and(|x| x == "dat") {
+            files.push((path, metadata.len(), metadata.modified()?));
+        }
+    }
+    Ok(())
+}
+
+/// Deletes the least recently updated threads while the cache exceeds `max_bytes`.
+fn evict(dir: &Path, max_bytes: u64) {
+    let mut files = Vec::new();
+    if let Err(e) = collect_dat_files(dir, &mut files) {
+        if e.kind() != io::ErrorKind::NotFound {
+            warn!("Failed to scan dat cache: {}", e);
+        }
+        return;
+    }
+    let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+    files.sort_by_key(|(_, _, modified)| *modified);
+    for (path, len, _) in files {
+        if total <= max_bytes {
+            break;
+        }
+        debug!("Evicting {}", path.display());
+        let _ = fs::remove_file(path.with_extension("json"));
+        if let Err(e) = fs::remove_file(&path) {
+            warn!("Failed to evict dat cache: {}", e);
+        }
+        total -= len;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("dat-cache-test-{}-{}", name, std::process::id()))
+    }
+
+    fn cursor(last_number: u32, dat_size: usize) -> ReadCursor {
+        ReadCursor {
+            last_number,
+            dat_size,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn appends_and_resumes() {
+        let dir = temp_dir("resume");
+        init(&dir);
+        let board_url = Url::parse("https://example.com/test/").unwrap();
+        assert!(load(&board_url, 1).is_none());
+
+        store(&board_url, 1, b"a<>1\n", false, &cursor(1, 5));
+        store(&board_url, 1, b"b<>2\n", true, &cursor(2, 10));
+        let (dat, loaded) = load(&board_url, 1).unwrap();
+        assert_eq!(dat, b"a<>1\nb<>2\n");
+        assert_eq!((loaded.last_number, loaded.dat_size), (2, 10));
+        assert!(dir.join("dat/example.com/test/1.dat").exists());
+
+        // A rewritten dat replaces the file.
+        store(&board_url, 1, b"c<>1\n", false, &cursor(1, 5));
+        assert_eq!(load(&board_url, 1).unwrap().0, b"c<>1\n");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn evicts_least_recently_updated() {
+        let dir = temp_dir("evict");
+        let now = SystemTime::now();
+        for (i, age) in [(1, 30), (2, 10), (3, 20)] {
+            let path = dir.join(format!("board/{}.dat", i));
+            write(&path, &[0; 100], false, &ReadCursor::default()).unwrap();
+            fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(now - Duration::from_secs(age))
+                .unwrap();
+        }
+        evict(&dir, 250);
+        assert!(!dir.join("board/1.dat").exists());
+        assert!(!dir.join("board/1.json").exists());
+        assert!(dir.join("board/2.dat").exists());
+        assert!(dir.join("board/3.dat").exists());
+        evict(&dir, 100);
+        assert!(dir.join("board/2.dat").exists());
+        assert!(!dir.join("board/3.dat").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
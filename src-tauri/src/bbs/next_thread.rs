@@ -0,0 +1,126 @@
+use std::collections::HashSet;
+
+use regex::Regex;
+
+use super::subject::SubjectEntry;
+
+const MIN_SIMILARITY: f64 = 0.5;
+
+fn normalize_title(title: &str) -> String {
+    Regex::new(r"[0-9０-９\s　【】\[\]()（）★☆|｜]|(?i)part")
+        .unwrap()
+        .replace_all(title, "")
+        .to_lowercase()
+}
+
+fn bigrams(text: &str) -> HashSet<(char, char)> {
+    let chars: Vec<_> = text.chars().collect();
+    chars.windows(2).map(|x| (x[0], x[1])).collect()
+}
+
+/// Sørensen–Dice coefficient over character bigrams.
+fn similarity(a: &str, b: &str) -> f64 {
+    let a = bigrams(&normalize_title(a));
+    let b = bigrams(&normalize_title(b));
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    2.0 * a.intersection(&b).count() as f64 / (a.len() + b.len()) as f64
+}
+
+fn part_number(title: &str) -> Option<u64> {
+    Regex::new(r"[0-9]+")
+        .unwrap()
+        .find_iter(title)
+        .last()?
+        .as_str()
+        .parse()
+        .ok()
+}
+
+/// Picks the most likely follow-up of `current_key` from subject.txt entries.
+pub fn pick_next_thread<'a>(
+    current_key: u64,
+    current_title: &str,
+    entries: &'a [SubjectEntry],
+) -> Option<&'a SubjectEntry> {
+    let mut candidates: Vec<_> = entries
+        .iter()
+        .filter(|entry| entry.key > current_key)
+        .map(|entry| (entry, similarity(current_title, &entry.title)))
+        .filter(|(_, similarity)| *similarity >= MIN_SIMILARITY)
+        .collect();
+    candidates.sort_by_key(|(entry, _)| entry.key);
+    let next_part = part_number(current_title).map(|x| x + 1);
+    let len = candidates.len() as f64;
+    candidates
+        .into_iter()
+        .enumerate()
+        .map(|(i, (entry, similarity))| {
+            let recency = (i + 1) as f64 / len;
+            let part_bonus = if next_part.is_some() && part_number(&entry.title) == next_part {
+                0.3
+            } else {
+                0.0
+            };
+            (entry, similarity + 0.1 * recency + part_bonus)
+        })
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(entry, _)| entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(list: &[(u64, &str)]) -> Vec<SubjectEntry> {
+        list.iter()
+            .map(|&(key, title)| SubjectEntry {
+                key,
+                title: title.to_owned(),
+                count: 1,
+            })
+            .collect()
+    }
+
+    fn next_key(key: u64, title: &str, list: &[(u64, &str)]) -> Option<u64> {
+        pick_next_thread(key, title, &entries(list)).map(|x| x.key)
+    }
+
+    #[test]
+    fn prefers_the_next_part() {
+        let list = [
+            (1729000000, "【配信】ゲーム実況スレ Part11"),
+            (1729222496, "【配信】ゲーム実況スレ Part13"),
+            (1729300000, "【配信】ゲーム実況スレ Part14"),
+            (1729400000, "今日の晩ごはんを報告するスレ"),
+        ];
+        assert_eq!(
+            next_key(1729100000, "【配信】ゲーム実況スレ Part12", &list),
+            Some(1729222496)
+        );
+    }
+
+    #[test]
+    fn rejects_unrelated_titles() {
+        let list = [
+            (1729222496, "今日の晩ごはんを報告するスレ"),
+            (1729300000, "【雑談】なんでも質問スレ★3"),
+        ];
+        assert_eq!(next_key(1729100000, "ゲーム実況スレ Part12", &list), None);
+        assert!(similarity("ゲーム実況スレ", "今日の晩ごはん") < MIN_SIMILARITY);
+    }
+
+    #[test]
+    fn breaks_ties_by_recency() {
+        let list = [
+            (1729000000, "ゲーム実況スレ"),
+            (1729222496, "ゲーム実況スレ"),
+            (1729300000, "ゲーム実況スレ"),
+        ];
+        assert_eq!(
+            next_key(1729100000, "ゲーム実況スレ", &list),
+            Some(1729300000)
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::HashSet;
+
+use log::warn;
+use regex::Regex;
+
+use super::Post;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NgTarget {
+    Name,
+    Mail,
+    Id,
+    Trip,
+    Body,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NgRule {
+    pub target: NgTarget,
+    pub pattern: String,
+    #[serde(default)]
+    pub regex: bool,
+    /// Also hides the replies to matched posts.
+    #[serde(default)]
+    pub chain: bool,
+    /// Milliseconds since the Unix epoch.
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+}
+
+enum Matcher {
+    Literal(String),
+    Regex(Regex),
+}
+
+impl Matcher {
+    fn is_match(&self, text: &str) -> bool {
+        match self {
+            Matcher::Literal(pattern) => text.contains(pattern.as_str()),
+            Matcher::Regex(re) => re.is_match(text),
+        }
+    }
+}
+
+fn trip(name: &str) -> Option<&str> {
+    name.split_once('◆').map(|(_, trip)| trip.trim())
+}
+
+pub struct NgFilter {
+    rules: Vec<(NgTarget, Matcher, bool)>,
+}
+
+impl NgFilter {
+    /// Drops expired rules and rules with an invalid regex.
+    pub fn new(rules: &[NgRule], now: u64) -> Self {
+        let rules = rules
+            .iter()
+            .filter(|rule| rule.expires_at.is_none_or(|x| x > now))
+            .filter(|rule| !rule.pattern.is_empty())
+            .filter_map(|rule| {
+                let matcher = if rule.regex {
+                    match Regex::new(&rule.pattern) {
+                        Ok(re) => Matcher::Regex(re),
+                        Err(e) => {
+                            warn!("Invalid NG pattern {}: {}", rule.pattern, e);
+                            return None;
+                        }
+                    }
+                } else {
+                    Matcher::Literal(rule.pattern.clone())
+                };
+                Some((rule.target, matcher, rule.chain))
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// Returns `Some(chain)` when any rule matches.
+    fn matches(&self, post: &Post) -> Option<bool> {
+        let mut matched = None;
+        for (target, matcher, chain) in &self.rules {
+            let text = match target {
+                NgTarget::Name => Some(post.name.as_str()),
+                NgTarget::Mail => Some(post.mail.as_str()),
+                NgTarget::Id => post.id.as_deref(),
+                NgTarget::Trip => trip(&post.name),
+                NgTarget::Body => Some(post.body.as_str()),
+            };
+            if text.is_some_and(|x| matcher.is_match(x)) {
+                matched = Some(matched.unwrap_or(false) || *chain);
+            }
+        }
+        matched
+    }
+
+    /// Removes matched posts. `chained` carries the posts whose replies are
+    /// hidden over successive calls for the same thread.
+    pub fn apply(&self, posts: Vec<Post>, chained: &mut HashSet<u32>) -> Vec<Post> {
+        posts
+            .into_iter()
+            .filter(|post| {
+                let replies_to_chained = post.anchors.iter().any(|x| chained.contains(x));
+                let chain = match self.matches(post) {
+                    Some(chain) => chain,
+                    None if replies_to_chained => true,
+                    None => return true,
+                };
+                if chain {
+                    chained.insert(post.number);
+                }
+                false
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post(number: u32, name: &str, id: Option<&str>, body: &str) -> Post {
+        Post {
+            number,
+            name: name.to_owned(),
+            mail: String::new(),
+            date: String::new(),
+            id: id.map(|x| x.to_owned()),
+            body: body.to_owned(),
+            anchors: super::super::anchor::parse_anchors(body),
+        }
+    }
+
+    fn rule(target: NgTarget, pattern: &str, regex: bool, chain: bool) -> NgRule {
+        NgRule {
+            target,
+            pattern: pattern.to_owned(),
+            regex,
+            chain,
+            expires_at: None,
+        }
+    }
+
+    fn numbers(posts: &[Post]) -> Vec<u32> {
+        posts.iter().map(|x| x.number).collect()
+    }
+
+    #[test]
+    fn filters_posts() {
+        let filter = NgFilter::new(
+            &[
+                rule(NgTarget::Body, "spam", false, false),
+                rule(NgTarget::Id, "^Abc", true, true),
+                rule(NgTarget::Trip, "Trip", false, false),
+                rule(NgTarget::Body, "(", true, false),
+            ],
+            0,
+        );
+        let posts = vec![
+            post(1, "名無し", Some("Abc123"), "hello"),
+            post(2, "名無し", None, "spam"),
+            post(3, "名無し", None, ">>1"),
+            post(4, "コテ ◆Trip", None, "hi"),
+            post(5, "名無し", None, "ok"),
+        ];
+        let mut chained = HashSet::new();
+        assert_eq!(numbers(&filter.apply(posts, &mut chained)), vec![5]);
+        // Replies to a chained post stay hidden in later batches.
+        let later = vec![
+            post(6, "名無し", None, ">>3"),
+            post(7, "名無し", None, ">>5"),
+        ];
+        assert_eq!(numbers(&filter.apply(later, &mut chained)), vec![7]);
+    }
+
+    #[test]
+    fn drops_expired_rules() {
+        let mut expired = rule(NgTarget::Body, "spam", false, false);
+        expired.expires_at = Some(100);
+        let filter = NgFilter::new(&[expired], 200);
+        let posts = vec![post(1, "名無し", None, "spam")];
+        assert_eq!(numbers(&filter.apply(posts, &mut HashSet::new())), vec![1]);
+    }
+}
@@ -0,0 +1,65 @@
+use regex::Regex;
+
+/// Boards print dates in JST.
+const JST_OFFSET_SECS: i64 = 9 * 60 * 60;
+
+/// Days from 1970-01-01 to the given date of the proleptic Gregorian calendar.
+pub fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Parses the date field of a post, e.g. `2024/10/18(金) 12:34:56.78`, into
+/// milliseconds since the Unix epoch.
+pub fn parse_post_date(date: &str) -> Option<u64> {
+    let c = Regex::new(
+        r"([0-9]{2,4})/([0-9]{1,2})/([0-9]{1,2})[^0-9]*([0-9]{1,2}):([0-9]{2}):([0-9]{2})(?:\.([0-9]{1,3}))?",
+    )
+    .unwrap()
+    .captures(date)?;
+    let number = |i| c.get(i).map_or(Some(0), |x| x.as_str().parse::<i64>().ok());
+    let mut year = number(1)?;
+    if year < 100 {
+        year += 2000;
+    }
+    let (month, day) = (number(2)?, number(3)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let secs = days_from_civil(year, month, day) * 86400
+        + number(4)? * 3600
+        + number(5)? * 60
+        + number(6)?
+        - JST_OFFSET_SECS;
+    let millis = c.get(7).map_or(Some(0), |x| {
+        // `.78` is 780 ms.
+        format!("{:0<3}", x.as_str()).parse::<i64>().ok()
+    })?;
+    u64::try_from(secs * 1000 + millis).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_post_dates_in_jst() {
+        assert_eq!(
+            parse_post_date("2024/10/18(金) 12:34:56.78 ID:abc"),
+            Some(1729222496780)
+        );
+        assert_eq!(
+            parse_post_date("24/10/18(金) 12:34:56"),
+            Some(1729222496000)
+        );
+        assert_eq!(
+            parse_post_date("2024/01/01(月) 00:00:00"),
+            Some(1704034800000)
+        );
+        assert_eq!(parse_post_date("あぼーん"), None);
+    }
+}
@@ -0,0 +1,198 @@
+use std::fmt;
+
+use regex::Regex;
+
+use super::html_to_text;
+
+const MAX_MESSAGE_LEN: usize = 200;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PostErrorKind {
+    RateLimited,
+    ConfirmationRequired,
+    Regulated,
+    ThreadStopped,
+    TooLong,
+    Unknown,
+}
+
+impl PostErrorKind {
+    fn label(self) -> &'static str {
+        match self {
+            PostErrorKind::RateLimited => "連続投稿規制",
+            PostErrorKind::ConfirmationRequired => "書き込み確認",
+            PostErrorKind::Regulated => "書き込み規制",
+            PostErrorKind::ThreadStopped => "スレッドストップ",
+            PostErrorKind::TooLong => "本文が長すぎます",
+            PostErrorKind::Unknown => "エラー",
+        }
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PostError {
+    pub kind: PostErrorKind,
+    pub message: String,
+}
+
+impl PostError {
+    pub fn new(kind: PostErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for PostError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.kind.label(), self.message)
+    }
+}
+
+impl std::error::Error for PostError {}
+
+/// The visible text of the page body.
+fn extract_text(html: &str) -> String {
+    let body = Regex::new(r"(?is)<body[^>]*>(.*)</body>")
+        .unwrap()
+        .captures(html)
+        .map_or(html, |c| c.get(1).unwrap().as_str());
+    let body = Regex::new(r"(?is)<(script|style)[^>]*>.*?</(script|style)>")
+        .unwrap()
+        .replace_all(body, "");
+    let text = html_to_text(&body);
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn classify(text: &str) -> Option<PostErrorKind> {
+    const KEYWORDS: &[(PostErrorKind, &[&str])] = &[
+        (
+            PostErrorKind::ThreadStopped,
+            &[
+                "スレッドストップ",
+                "スレッドが停止",
+                "過去ログ",
+                "書き込めないスレッド",
+            ],
+        ),
+        (
+            PostErrorKind::TooLong,
+            &["長すぎ", "改行が多すぎ", "行数が多すぎ"],
+        ),
+        (
+            PostErrorKind::RateLimited,
+            &[
+                "連続投稿",
+                "連投",
+                "時間を置いて",
+                "しばらくたってから",
+                "Samba",
+            ],
+        ),
+        (
+            PostErrorKind::ConfirmationRequired,
+            &["書き込み確認", "書きこみ確認"],
+        ),
+        (
+            PostErrorKind::Regulated,
+            &["規制", "アクセス禁止", "アク禁", "投稿を拒否"],
+        ),
+    ];
+    KEYWORDS
+        .iter()
+        .find(|(_, keywords)| keywords.iter().any(|x| text.contains(x)))
+        .map(|(kind, _)| *kind)
+}
+
+/// Classifies a bbs.cgi / write.cgi response page, which is 200 even on failure.
+/// Only pages marked as errors are classified, since a successful page may
+/// still link to e.g. 過去ログ.
+pub fn check_post_response(html: &str) -> Result<(), PostError> {
+    let marker = Regex::new(r"2ch_X:([a-z]+)")
+        .unwrap()
+        .captures(html)
+        .map(|c| c[1].to_owned());
+    if matches!(marker.as_deref(), Some("true" | "false")) {
+        return Ok(());
+    }
+    let title = Regex::new(r"(?is)<title>(.*?)</title>")
+        .unwrap()
+        .captures(html)
+        .map(|c| html_to_text(&c[1]))
+        .unwrap_or_default();
+    if title.contains("書きこみました") || title.contains("書き込みました") {
+        return Ok(());
+    }
+    let text = extract_text(html);
+    let is_error = matches!(marker.as_deref(), Some("error" | "check" | "cookie"))
+        || [
+            "ＥＲＲＯＲ",
+            "ERROR",
+            "エラー",
+            "書き込み確認",
+            "書きこみ確認",
+        ]
+        .iter()
+        .any(|x| title.contains(x))
+        || ["ＥＲＲＯＲ：", "ERROR:"].iter().any(|x| text.contains(x));
+    if !is_error {
+        return Ok(());
+    }
+    let kind = match marker.as_deref() {
+        Some("cookie") => Some(PostErrorKind::ConfirmationRequired),
+        _ => classify(&format!("{} {}", title, text)),
+    };
+    let message: String = text.chars().take(MAX_MESSAGE_LEN).collect();
+    Err(PostError::new(
+        kind.unwrap_or(PostErrorKind::Unknown),
+        message,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind(html: &str) -> Option<PostErrorKind> {
+        check_post_response(html).err().map(|x| x.kind)
+    }
+
+    #[test]
+    fn accepts_success_pages() {
+        assert_eq!(
+            kind("<html><!-- 2ch_X:true --><title>書きこみました。</title><body>過去ログ</body></html>"),
+            None
+        );
+        assert_eq!(
+            kind("<html><head><title>書き込み完了</title></head><body><a href=\"/kako/\">過去ログ</a></body></html>"),
+            None
+        );
+    }
+
+    #[test]
+    fn classifies_errors() {
+        assert_eq!(
+            kind("<html><!-- 2ch_X:error --><title>ＥＲＲＯＲ！</title><body>ＥＲＲＯＲ：このスレッドは過去ログ倉庫に格納されています。</body></html>"),
+            Some(PostErrorKind::ThreadStopped)
+        );
+        assert_eq!(
+            kind("<html><!-- 2ch_X:cookie --><title>■ 書き込み確認 ■</title><body>規制</body></html>"),
+            Some(PostErrorKind::ConfirmationRequired)
+        );
+        assert_eq!(
+            kind("<title>ERROR!</title><body>ERROR: 連続投稿ですか？</body>"),
+            Some(PostErrorKind::RateLimited)
+        );
+        assert_eq!(
+            kind("<title>ＥＲＲＯＲ！</title><body>ＥＲＲＯＲ：本文が長すぎます！</body>"),
+            Some(PostErrorKind::TooLong)
+        );
+        assert_eq!(
+            kind("<title>ＥＲＲＯＲ！</title><body>ＥＲＲＯＲ：不明</body>"),
+            Some(PostErrorKind::Unknown)
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use regex::Regex;
+use url::Url;
+
+/// Posts requested by a read.cgi URL, e.g. `l50`, `10-20`, `-100`, `100-` or `50n`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadRange {
+    /// `lN`: the last N posts.
+    pub last: Option<u32>,
+    pub from: Option<u32>,
+    pub to: Option<u32>,
+    /// `n`: leaves out the first post.
+    pub no_first: bool,
+}
+
+impl ReadRange {
+    /// Parses the path segment following the thread key.
+    pub fn parse(text: &str) -> Option<ReadRange> {
+        let c = Regex::new(r"^(?:l([0-9]+)|([0-9]*)(-?)([0-9]*))(n?)$")
+            .unwrap()
+            .captures(text)?;
+        let number = |i| c.get(i).and_then(|x| x.as_str().parse().ok());
+        let mut range = ReadRange {
+            last: number(1),
+            from: number(2),
+            to: number(4),
+            no_first: !c[5].is_empty(),
+        };
+        if c.get(3).is_some_and(|x| x.is_empty()) {
+            // A single number.
+            range.to = range.from;
+        }
+        Some(range).filter(|x| *x != ReadRange::default())
+    }
+
+    /// Parses the `ls`, `st`, `to` and `nofirst` parameters of `read.cgi?bbs=...&key=...`.
+    pub fn from_query(url: &Url) -> Option<ReadRange> {
+        let mut range = ReadRange::default();
+        for (name, value) in url.query_pairs() {
+            match name.as_ref() {
+                "ls" => range.last = value.parse().ok(),
+                "st" => range.from = value.parse().ok(),
+                "to" => range.to = value.parse().ok(),
+                "nofirst" => range.no_first = value == "true",
+                _ => {}
+            }
+        }
+        Some(range).filter(|x| *x != ReadRange::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(last: Option<u32>, from: Option<u32>, to: Option<u32>, no_first: bool) -> ReadRange {
+        ReadRange {
+            last,
+            from,
+            to,
+            no_first,
+        }
+    }
+
+    #[test]
+    fn parses_path_segments() {
+        assert_eq!(
+            ReadRange::parse("l50"),
+            Some(range(Some(50), None, None, false))
+        );
+        assert_eq!(
+            ReadRange::parse("10-20"),
+            Some(range(None, Some(10), Some(20), false))
+        );
+        assert_eq!(
+            ReadRange::parse("-100"),
+            Some(range(None, None, Some(100), false))
+        );
+        assert_eq!(
+            ReadRange::parse("100-"),
+            Some(range(None, Some(100), None, false))
+        );
+        assert_eq!(
+            ReadRange::parse("50n"),
+            Some(range(None, Some(50), Some(50), true))
+        );
+        assert_eq!(ReadRange::parse(""), None);
+        assert_eq!(ReadRange::parse("abc"), None);
+    }
+
+    #[test]
+    fn parses_query() {
+        let url =
+            Url::parse("http://example.com/test/read.cgi?bbs=a&key=1&ls=50&nofirst=true").unwrap();
+        assert_eq!(
+            ReadRange::from_query(&url),
+            Some(range(Some(50), None, None, true))
+        );
+        let url = Url::parse("http://example.com/test/read.cgi?bbs=a&key=1").unwrap();
+        assert_eq!(ReadRange::from_query(&url), None);
+    }
+}
@@ -0,0 +1,127 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Mutex, OnceLock},
+};
+
+use anyhow::Result;
+use encoding_rs::Encoding;
+
+use super::{post_error::PostErrorKind, PostError, DEFAULT_THREAD_STOP};
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BoardSetting {
+    pub noname_name: Option<String>,
+    /// Maximum message size in bytes of the board charset.
+    pub message_count: Option<u32>,
+    /// Half of the maximum number of lines, as in 2ch.
+    pub line_number: Option<u32>,
+    pub subject_count: Option<u32>,
+    pub thread_stop: u32,
+    /// Seconds to wait between posts (`BBS_SAMBA24`).
+    pub post_interval: Option<u32>,
+}
+
+impl BoardSetting {
+    pub fn validate_message(
+        &self,
+        encoding: &'static Encoding,
+        msg: &str,
+    ) -> Result<(), PostError> {
+        if let Some(message_count) = self.message_count {
+            let len = encoding.encode(msg).0.len();
+            if len > message_count as usize {
+                return Err(PostError::new(
+                    PostErrorKind::TooLong,
+                    format!("{} / {} バイト", len, message_count),
+                ));
+            }
+        }
+        if let Some(line_number) = self.line_number {
+            let lines = msg.lines().count();
+            if lines > line_number as usize * 2 {
+                return Err(PostError::new(
+                    PostErrorKind::TooLong,
+                    format!("{} / {} 行", lines, line_number * 2),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses `KEY=value` lines of SETTING.TXT and Shitaraba's setting.cgi.
+pub fn parse_setting_txt(setting_txt: &str) -> BoardSetting {
+    let values: HashMap<_, _> = setting_txt
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .collect();
+    let number = |key: &str| values.get(key).and_then(|x| x.parse().ok());
+    BoardSetting {
+        noname_name: values.get("BBS_NONAME_NAME").map(|x| x.to_string()),
+        message_count: number("BBS_MESSAGE_COUNT"),
+        line_number: number("BBS_LINE_NUMBER"),
+        subject_count: number("BBS_SUBJECT_COUNT"),
+        thread_stop: number("BBS_THREAD_STOP").unwrap_or(DEFAULT_THREAD_STOP),
+        post_interval: number("BBS_SAMBA24"),
+    }
+}
+
+static CACHE: OnceLock<Mutex<HashMap<String, BoardSetting>>> = OnceLock::new();
+
+/// Runs `fetch` only the first time for each board.
+pub async fn cached_setting(
+    board_url: String,
+    fetch: impl Future<Output = Result<String>>,
+) -> Result<BoardSetting> {
+    let cache = CACHE.get_or_init(Default::default);
+    if let Some(setting) = cache.lock().unwrap().get(&board_url) {
+        return Ok(setting.clone());
+    }
+    let setting = parse_setting_txt(&fetch.await?);
+    cache.lock().unwrap().insert(board_url, setting.clone());
+    Ok(setting)
+}
+
+#[cfg(test)]
+mod tests {
+    use encoding_rs::{SHIFT_JIS, UTF_8};
+
+    use super::*;
+
+    #[test]
+    fn parses_setting_txt() {
+        let setting = parse_setting_txt(
+            "test@test\nBBS_TITLE=テスト板\nBBS_NONAME_NAME=名無しさん\nBBS_MESSAGE_COUNT=2048\nBBS_LINE_NUMBER=16\nBBS_SUBJECT_COUNT=abc\nBBS_SAMBA24=30\n",
+        );
+        assert_eq!(setting.noname_name.as_deref(), Some("名無しさん"));
+        assert_eq!(setting.message_count, Some(2048));
+        assert_eq!(setting.line_number, Some(16));
+        assert_eq!(setting.subject_count, None);
+        assert_eq!(setting.post_interval, Some(30));
+        assert_eq!(setting.thread_stop, DEFAULT_THREAD_STOP);
+        assert_eq!(DEFAULT_THREAD_STOP, 1000);
+
+        let setting = parse_setting_txt("BBS_THREAD_STOP=500\r\n");
+        assert_eq!(setting.thread_stop, 500);
+        assert_eq!(setting.noname_name, None);
+    }
+
+    #[test]
+    fn validates_messages() {
+        let setting = parse_setting_txt("BBS_MESSAGE_COUNT=6\nBBS_LINE_NUMBER=1\n");
+        assert!(setting.validate_message(SHIFT_JIS, "あいう").is_ok());
+        // 3 characters are 9 bytes in UTF-8.
+        let e = setting.validate_message(UTF_8, "あいう").unwrap_err();
+        assert_eq!(e.kind, PostErrorKind::TooLong);
+        assert_eq!(e.message, "9 / 6 バイト");
+        assert!(setting.validate_message(UTF_8, "a\nb").is_ok());
+        let e = setting.validate_message(UTF_8, "a\nb\nc").unwrap_err();
+        assert_eq!(e.message, "3 / 2 行");
+        assert!(parse_setting_txt("")
+            .validate_message(UTF_8, &"a\n".repeat(100))
+            .is_ok());
+    }
+}
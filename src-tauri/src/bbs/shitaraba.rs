@@ -1,100 +1,246 @@
-use anyhow::{anyhow, bail, Result};
-use encoding_rs::Encoding;
+use anyhow::Result;
+use encoding_rs::{Encoding, EUC_JP};
 use log::debug;
-use percent_encoding::{percent_encode, NON_ALPHANUMERIC};
 use regex::Regex;
 use reqwest::header::{CONTENT_TYPE, REFERER, USER_AGENT};
 use url::Url;
 
-use super::{Thread, UA};
+use super::{
+    anchor::parse_anchors,
+    backend::Backend,
+    charset, client, dat_cache, fetch_text, html_to_text,
+    post_error::check_post_response,
+    read_range::ReadRange,
+    setting::{cached_setting, BoardSetting},
+    subject::{parse_subject_txt, SubjectEntry},
+    validate_message, Board, Post, ReadCursor, Thread, ThreadNotFound, UA,
+};
 
-pub fn parse_thread_url(thread_url: &Url) -> Option<Shitaraba> {
+pub struct ShitarabaBackend;
+
+impl Backend for ShitarabaBackend {
+    fn matches(&self, url: &Url) -> bool {
+        url.host_str() == Some("jbbs.shitaraba.net") || url.path().starts_with("/bbs/read.cgi/")
+    }
+
+    fn parse_thread_url(&self, url: &Url) -> Option<Box<dyn Thread>> {
+        Some(Box::new(parse_thread_url(url)?))
+    }
+
+    fn parse_board_url(&self, url: &Url) -> Option<Box<dyn Board>> {
+        Some(Box::new(parse_board_url(url)?))
+    }
+}
+
+fn parse_thread_url(thread_url: &Url) -> Option<Shitaraba> {
     let origin = thread_url.origin().ascii_serialization();
-    let c = Regex::new(r"^/bbs/read.cgi/(.+?)/(.+?)/(.+?)(:?/.*)?$")
+    let c = Regex::new(r"^/bbs/read\.cgi/([^/]+)/([0-9]+)/([0-9]+)(?:/([^/]*))?")
         .unwrap()
         .captures(thread_url.path())?;
-    let dir = c.get(1).unwrap().as_str().to_string();
-    let bbs = c.get(2).unwrap().as_str().parse().ok()?;
-    let key = c.get(3).unwrap().as_str().parse().ok()?;
+    let dir = c[1].to_owned();
+    let bbs = c[2].parse().ok()?;
+    let key = c[3].parse().ok()?;
+    let range = c.get(4).and_then(|x| ReadRange::parse(x.as_str()));
     Some(Shitaraba {
-        origin,
-        dir,
-        bbs,
+        board: ShitarabaBoard { origin, dir, bbs },
         key,
+        range,
     })
 }
 
-pub fn parse_board_url(board_url: &Url) -> Option<(String, u64)> {
+fn parse_board_url(board_url: &Url) -> Option<ShitarabaBoard> {
     let c = Regex::new(r"^https://jbbs.shitaraba.net/(.+?)/([0-9]+)/?$")
         .unwrap()
         .captures(board_url.as_str())?;
-    Some((
-        c.get(1).unwrap().as_str().to_string(),
-        c.get(2).unwrap().as_str().parse().ok()?,
-    ))
+    Some(ShitarabaBoard {
+        origin: board_url.origin().ascii_serialization(),
+        dir: c.get(1).unwrap().as_str().to_string(),
+        bbs: c.get(2).unwrap().as_str().parse().ok()?,
+    })
 }
 
 async fn fetch_subject_txt(origin: &str, dir: &str, bbs: u64) -> Result<String> {
-    let subject_url = format!("{}/{}/{}/subject.txt", origin, dir, bbs);
-    Ok(reqwest::get(subject_url).await?.text().await?)
+    fetch_text(&format!("{}/{}/{}/subject.txt", origin, dir, bbs), EUC_JP).await
 }
 
-fn read_latest_thread(subject_txt: &str) -> Result<u64> {
-    Ok(subject_txt
-        .lines()
-        .next()
-        .ok_or_else(|| anyhow!("Empty subject.txt"))?
-        .split(".")
-        .next()
-        .ok_or_else(|| anyhow!("Empty subject.txt"))?
-        .parse()?)
+fn thread_url(origin: &str, dir: &str, bbs: u64, key: u64) -> Url {
+    let thread_url = format!("{}/bbs/read.cgi/{}/{}/{}/", origin, dir, bbs, key);
+    Url::parse(&thread_url).unwrap()
 }
 
-async fn fetch_latest_thread(origin: &str, dir: &str, bbs: u64) -> Result<u64> {
-    let subject_txt = fetch_subject_txt(origin, dir, bbs).await?;
-    read_latest_thread(&subject_txt)
+async fn fetch_setting(origin: &str, dir: &str, bbs: u64) -> Result<String> {
+    let url = format!("{}/bbs/api/setting.cgi/{}/{}/", origin, dir, bbs);
+    fetch_text(&url, EUC_JP).await
 }
 
-pub async fn fetch_latest_thread_url(origin: &str, dir: &str, bbs: u64) -> Result<Url> {
-    let key = fetch_latest_thread(origin, dir, bbs)
-        .await
-        .inspect_err(|err| log::trace!("{:?}", err))?;
-    let thread_url = format!("{}/bbs/read.cgi/{}/{}/{}/", origin, dir, bbs, key);
-    Ok(Url::parse(&thread_url).unwrap())
+fn parse_rawmode_title(rawmode: &str) -> Option<String> {
+    rawmode
+        .lines()
+        .filter_map(|line| line.split("<>").nth(5))
+        .find(|x| !x.is_empty())
+        .map(html_to_text)
 }
 
-fn charset_percent_encode(encoding: &'static Encoding, text: &str) -> String {
-    let (text, _, _) = encoding.encode(text);
-    percent_encode(&text, NON_ALPHANUMERIC).to_string()
+fn parse_rawmode(rawmode: &str) -> Vec<Post> {
+    rawmode
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split("<>");
+            let number = fields.next()?.parse().ok()?;
+            let name = fields.next().unwrap_or_default();
+            let mail = fields.next().unwrap_or_default();
+            let date = fields.next().unwrap_or_default();
+            let body = html_to_text(fields.next().unwrap_or_default().trim());
+            let _title = fields.next();
+            let id = fields.next().filter(|x| !x.is_empty());
+            Some(Post {
+                number,
+                name: html_to_text(name),
+                mail: html_to_text(mail),
+                date: date.to_owned(),
+                id: id.map(|x| x.to_owned()),
+                anchors: parse_anchors(&body),
+                body,
+            })
+        })
+        .collect()
 }
 
-pub struct Shitaraba {
+pub struct ShitarabaBoard {
     origin: String,
     dir: String,
     bbs: u64,
+}
+
+#[async_trait::async_trait]
+impl Board for ShitarabaBoard {
+    fn board_url(&self) -> Url {
+        Url::parse(&format!("{}/{}/{}/", self.origin, self.dir, self.bbs)).unwrap()
+    }
+
+    fn thread_url(&self, key: u64) -> Url {
+        thread_url(&self.origin, &self.dir, self.bbs, key)
+    }
+
+    async fn fetch_subject(&self) -> Result<Vec<SubjectEntry>> {
+        let subject_txt = fetch_subject_txt(&self.origin, &self.dir, self.bbs).await?;
+        Ok(parse_subject_txt(&subject_txt))
+    }
+
+    async fn fetch_setting(&self) -> Result<BoardSetting> {
+        cached_setting(
+            self.board_url().to_string(),
+            fetch_setting(&self.origin, &self.dir, self.bbs),
+        )
+        .await
+    }
+
+    fn encoding(&self) -> &'static Encoding {
+        EUC_JP
+    }
+}
+
+pub struct Shitaraba {
+    board: ShitarabaBoard,
     key: u64,
+    range: Option<ReadRange>,
 }
 
-impl Shitaraba {
-    pub async fn new(url: &Url) -> Result<Self> {
-        if let Some(site) = parse_thread_url(url) {
-            return Ok(site);
-        }
-        bail!("Invalid URL: {}", url);
+#[async_trait::async_trait]
+impl Board for Shitaraba {
+    fn board_url(&self) -> Url {
+        self.board.board_url()
+    }
+
+    fn thread_url(&self, key: u64) -> Url {
+        self.board.thread_url(key)
+    }
+
+    async fn fetch_subject(&self) -> Result<Vec<SubjectEntry>> {
+        self.board.fetch_subject().await
+    }
+
+    async fn fetch_setting(&self) -> Result<BoardSetting> {
+        self.board.fetch_setting().await
+    }
+
+    fn encoding(&self) -> &'static Encoding {
+        self.board.encoding()
     }
 }
 
 #[async_trait::async_trait]
 impl Thread for Shitaraba {
+    fn key(&self) -> u64 {
+        self.key
+    }
+
+    fn read_range(&self) -> Option<ReadRange> {
+        self.range
+    }
+
+    fn load_cached(&self, charset: &str) -> Option<(Vec<Post>, ReadCursor)> {
+        let encoding = charset::lookup_encoding(charset).ok()?;
+        let (rawmode, cursor) = dat_cache::load(&self.board_url(), self.key)?;
+        Some((
+            parse_rawmode(&charset::decode(None, &rawmode, Some(encoding))),
+            cursor,
+        ))
+    }
+
+    async fn fetch_new_posts(
+        &self,
+        charset: &str,
+        cursor: &mut ReadCursor,
+    ) -> Result<Option<Vec<Post>>> {
+        let encoding = charset::lookup_encoding(charset)?;
+        let resp = client()
+            .get(format!(
+                "{}/bbs/rawmode.cgi/{}/{}/{}/{}-",
+                self.board.origin,
+                self.board.dir,
+                self.board.bbs,
+                self.key,
+                cursor.last_number + 1
+            ))
+            .header(USER_AGENT, UA)
+            .send()
+            .await?
+            .error_for_status()?;
+        // e.g. "KEY NOT FOUND", "STORAGE IN"
+        if let Some(error) = resp.headers().get("ERROR") {
+            debug!("rawmode error: {:?}", error);
+            return Err(ThreadNotFound.into());
+        }
+        let headers = resp.headers().clone();
+        let bytes = resp.bytes().await?;
+        let rawmode = charset::decode(Some(&headers), &bytes, Some(encoding));
+        if let Some(title) = parse_rawmode_title(&rawmode) {
+            cursor.title = Some(title);
+        }
+        let posts: Vec<_> = parse_rawmode(&rawmode)
+            .into_iter()
+            .filter(|post| post.number > cursor.last_number)
+            .collect();
+        let Some(last) = posts.last() else {
+            return Ok(None);
+        };
+        let append = cursor.last_number > 0;
+        cursor.last_number = last.number;
+        dat_cache::store(&self.board_url(), self.key, &bytes, append, cursor);
+        Ok(Some(posts))
+    }
+
     async fn post(&self, charset: &str, name: &str, email: &str, msg: &str) -> Result<()> {
-        let encoding = Encoding::for_label(charset.as_bytes()).unwrap();
-        let name = charset_percent_encode(encoding, name);
-        let email = charset_percent_encode(encoding, email);
-        let msg = charset_percent_encode(encoding, msg);
-        let resp = reqwest::Client::new()
+        let encoding = charset::lookup_encoding(charset)?;
+        validate_message(self, encoding, msg).await?;
+        let name = charset::percent_encode_in(encoding, name);
+        let email = charset::percent_encode_in(encoding, email);
+        let msg = charset::percent_encode_in(encoding, msg);
+        let resp = client()
             .post(format!(
                 "{}/bbs/write.cgi/{}/{}/{}/",
-                self.origin, self.dir, self.bbs, self.key
+                self.board.origin, self.board.dir, self.board.bbs, self.key
             ))
             .header(
                 CONTENT_TYPE,
@@ -104,20 +250,58 @@ impl Thread for Shitaraba {
                 REFERER,
                 format!(
                     "{}/bbs/read.cgi/{}/{}/{}/",
-                    self.origin, self.dir, self.bbs, self.key
+                    self.board.origin, self.board.dir, self.board.bbs, self.key
                 ),
             )
             .header(USER_AGENT, UA)
             .body(format!(
                 "BBS={}&KEY={}&DIR={}&NAME={}&MAIL={}&MESSAGE={}",
-                self.bbs, self.key, self.dir, name, email, msg
+                self.board.bbs, self.key, self.board.dir, name, email, msg
             ))
             .send()
             .await?
             .error_for_status()?;
+        let headers = resp.headers().clone();
         let bytes = resp.bytes().await?;
-        let text = Encoding::for_label(b"euc-jp").unwrap().decode(&bytes).0;
-        debug!("post resp: {}", text.to_string());
+        let text = charset::decode(Some(&headers), &bytes, Some(encoding));
+        debug!("post resp: {}", text);
+        check_post_response(&text)?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rawmode() {
+        let rawmode = concat!(
+            "1<>名無し<>sage<>2024/10/18(金) 12:34:56<>本文<br>&gt;&gt;2<>スレタイ<>Abc123\n",
+            "2<>名無し<><>2024/10/18(金) 12:35:00<>返信<><>\n",
+            "broken\n",
+        );
+        let posts = parse_rawmode(rawmode);
+        assert_eq!(posts.len(), 2);
+        assert_eq!(posts[0].number, 1);
+        assert_eq!(posts[0].mail, "sage");
+        assert_eq!(posts[0].body, "本文\n>>2");
+        assert_eq!(posts[0].anchors, vec![2]);
+        assert_eq!(posts[0].id.as_deref(), Some("Abc123"));
+        assert_eq!(posts[1].id, None);
+        assert_eq!(parse_rawmode_title(rawmode).as_deref(), Some("スレタイ"));
+    }
+
+    #[test]
+    fn parses_thread_urls() {
+        let url = Url::parse("https://jbbs.shitaraba.net/bbs/read.cgi/game/12345/1729222496/l10")
+            .unwrap();
+        let thread = parse_thread_url(&url).unwrap();
+        assert_eq!(thread.board.dir, "game");
+        assert_eq!(thread.board.bbs, 12345);
+        assert_eq!(thread.key, 1729222496);
+        assert_eq!(thread.range, ReadRange::parse("l10"));
+        let url = Url::parse("https://jbbs.shitaraba.net/game/12345/").unwrap();
+        assert!(parse_board_url(&url).is_some());
+    }
+}
@@ -0,0 +1,49 @@
+use std::collections::HashSet;
+
+use regex::Regex;
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubjectEntry {
+    pub key: u64,
+    pub title: String,
+    pub count: u32,
+}
+
+/// Parses both `<key>.dat<>title (n)` and `<key>.cgi,title(n)` lines.
+pub fn parse_subject_txt(subject_txt: &str) -> Vec<SubjectEntry> {
+    let re = Regex::new(r"^([0-9]+)\.(?:dat<>|cgi,)(.*?) ?\(([0-9]+)\)$").unwrap();
+    let mut keys = HashSet::new();
+    subject_txt
+        .lines()
+        .filter_map(|line| {
+            let c = re.captures(line.trim_end())?;
+            Some(SubjectEntry {
+                key: c[1].parse().ok()?,
+                title: super::decode_entities(&c[2]),
+                count: c[3].parse().ok()?,
+            })
+        })
+        // Shitaraba repeats the first thread on the last line.
+        .filter(|entry| keys.insert(entry.key))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_both_formats() {
+        let entries = parse_subject_txt(
+            "1729222496.dat<>実況スレ &amp; 雑談 (123)\n1234567890.cgi,したらば(45)\n1234567890.cgi,したらば(45)\nbroken line\n",
+        );
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].key, 1729222496);
+        assert_eq!(entries[0].title, "実況スレ & 雑談");
+        assert_eq!(entries[0].count, 123);
+        assert_eq!(entries[1].key, 1234567890);
+        assert_eq!(entries[1].title, "したらば");
+        assert_eq!(entries[1].count, 45);
+    }
+}
@@ -1,5 +1,11 @@
+pub mod channel_refresher;
 pub mod commands;
 pub mod platform;
+pub mod post_history;
+pub mod post_queue;
 pub mod resolve_url;
+pub mod settings;
+pub mod stall_detector;
 pub mod state;
+pub mod thread_poller;
 pub mod window;
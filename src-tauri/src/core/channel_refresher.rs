@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+use log::{info, warn};
+use tauri::{async_runtime::JoinHandle, AppHandle, Emitter, Manager};
+
+use super::{
+    state::{state, StreamInfo},
+    thread_poller::{bind_thread, emit_thread_changed, resolve_contact_thread},
+    window::fetch_channel_details,
+};
+
+const INTERVAL: Duration = Duration::from_secs(60);
+
+fn current_stream_info(app_handle: &AppHandle, label: &str) -> Option<StreamInfo> {
+    let state = state(app_handle);
+    let mut state = state.lock().unwrap();
+    Some(state.try_window_state_mut(label)?.stream_info.clone())
+}
+
+/// Stores `new` unless the window started playing another stream meanwhile.
+fn replace_stream_info(
+    app_handle: &AppHandle,
+    label: &str,
+    old: &StreamInfo,
+    new: &StreamInfo,
+) -> bool {
+    let state = state(app_handle);
+    let mut state = state.lock().unwrap();
+    match state.try_window_state_mut(label) {
+        Some(state) if state.stream_info.url == old.url => {
+            state.stream_info = new.clone();
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Follows the thread the streamer moved to.
+async fn rebind_contact_url(app_handle: &AppHandle, label: &str, contact_url: &str) {
+    if let Some((thread_url, charset, thread_name)) = resolve_contact_thread(contact_url).await {
+        info!("Contact URL changed: {}", thread_url);
+        bind_thread(app_handle, label, thread_url.clone(), charset.clone());
+        emit_thread_changed(app_handle, label, &thread_url, &charset, thread_name);
+    }
+}
+
+async fn run(app_handle: AppHandle, label: String) {
+    while app_handle.get_webview_window(&label).is_some() {
+        tokio::time::sleep(INTERVAL).await;
+        let Some(old) = current_stream_info(&app_handle, &label) else {
+            return;
+        };
+        let Some(details) = fetch_channel_details(&app_handle, &old).await else {
+            continue;
+        };
+        let mut new = old.clone();
+        new.apply_channel_info(&details);
+        if new == old || !replace_stream_info(&app_handle, &label, &old, &new) {
+            continue;
+        }
+        if let Err(e) = app_handle.emit_to(&label, "stream-info-changed", new.clone()) {
+            warn!("Failed to emit stream-info-changed: {}", e);
+        }
+        if new.contact_url != old.contact_url {
+            if let Some(contact_url) = &new.contact_url {
+                rebind_contact_url(&app_handle, &label, contact_url).await;
+            }
+        }
+    }
+}
+
+/// Polls the local node for the channel played in a window and applies changes
+/// of its name, genre, description, comment and contact URL.
+pub struct ChannelRefresher {
+    task: JoinHandle<()>,
+}
+
+impl ChannelRefresher {
+    pub fn start(app_handle: AppHandle, label: String) -> Self {
+        let task = tauri::async_runtime::spawn(run(app_handle, label));
+        Self { task }
+    }
+}
+
+impl Drop for ChannelRefresher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
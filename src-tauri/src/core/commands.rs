@@ -1,16 +1,57 @@
+use std::collections::HashSet;
+
 use anyhow::Result;
 use log::error;
-use tauri::{Manager, Window};
+use tauri::{AppHandle, Manager, Window};
 use url::Url;
 
-use crate::bbs;
+use crate::{
+    bbs::{
+        self, lookup_encoding, unencodable_chars, BoardSetting, Post, PostError, PostErrorKind,
+        ReadRange, SubjectEntry,
+    },
+    peercast::{parse_channel_url, ChannelStatus, PeerCastClient, RelayNode},
+    yp::{self, YpChannel},
+};
 
 use super::{
+    channel_refresher::ChannelRefresher,
+    post_history::{post_and_record, post_history, PostHistoryEntry, PostHistoryQuery},
+    post_queue::{self, post_queue, PostDraft},
     resolve_url::{self, UrlType},
+    settings::{ng_filter, resolve_identity, settings, Settings},
+    stall_detector::StallDetector,
     state::{state, StreamInfo},
-    window::{set_video_size, update_height, window_frame_size},
+    thread_poller::{bind_thread, emit_thread_changed, resolve_contact_thread},
+    window::{
+        fetch_channel_details, open_player_window, set_video_size, update_height, window_frame_size,
+    },
 };
 
+fn is_thread_bound(app_handle: &AppHandle, label: &str) -> Option<bool> {
+    let state = state(app_handle);
+    let mut state = state.lock().unwrap();
+    Some(state.try_window_state_mut(label)?.thread.is_some())
+}
+
+/// Binds the window to the thread of the channel's contact URL unless it
+/// already follows one, and tells the frontend with `thread-changed`.
+async fn bind_contact_thread(app_handle: AppHandle, label: String, contact_url: String) {
+    if is_thread_bound(&app_handle, &label) != Some(false) {
+        return;
+    }
+    let Some((thread_url, charset, thread_name)) = resolve_contact_thread(&contact_url).await
+    else {
+        return;
+    };
+    // The window may have been closed or bound by the user meanwhile.
+    if is_thread_bound(&app_handle, &label) != Some(false) {
+        return;
+    }
+    bind_thread(&app_handle, &label, thread_url.clone(), charset.clone());
+    emit_thread_changed(&app_handle, &label, &thread_url, &charset, thread_name);
+}
+
 #[tauri::command(rename_all = "camelCase")]
 pub async fn initialize(
     window: Window,
@@ -21,19 +62,39 @@ pub async fn initialize(
 ) -> Result<StreamInfo, ()> {
     let window_frame_size = window_frame_size(&window, inner_width, inner_height);
     let state = state(window.app_handle());
+    let stream_info = state
+        .lock()
+        .unwrap()
+        .window_state_mut(window.label())
+        .stream_info
+        .clone();
+    let details = fetch_channel_details(window.app_handle(), &stream_info).await;
     let mut state = state.lock().unwrap();
-    let state = state.window_state_mut(window.label());
-    let Some(window_frame_size) = window_frame_size else {
-        return Ok(state.stream_info.clone());
-    };
-    if state.resizer.is_initialized_window_frame_size() {
-        return Ok(state.stream_info.clone());
+    // The window may have been closed while the node was asked.
+    let state = state.try_window_state_mut(window.label()).ok_or(())?;
+    if let Some(details) = &details {
+        state.stream_info.apply_channel_info(details);
+    }
+    if state.channel_refresher.is_none() {
+        state.channel_refresher = Some(ChannelRefresher::start(
+            window.app_handle().to_owned(),
+            window.label().to_owned(),
+        ));
+    }
+    if let Some(contact_url) = state.stream_info.contact_url.clone() {
+        tauri::async_runtime::spawn(bind_contact_thread(
+            window.app_handle().to_owned(),
+            window.label().to_owned(),
+            contact_url,
+        ));
+    }
+    if let Some(window_frame_size) = window_frame_size {
+        if !state.resizer.is_initialized_window_frame_size() {
+            state.resizer.init_window_frame_size(window_frame_size);
+            set_video_size(state, video_client_width, video_client_height);
+            update_height(&window, state);
+        }
     }
-
-    state.resizer.init_window_frame_size(window_frame_size);
-    set_video_size(state, video_client_width, video_client_height);
-    update_height(&window, state);
-
     Ok(state.stream_info.clone())
 }
 
@@ -45,16 +106,26 @@ pub enum ResolveUrlResult {
         thread_url: String,
         charset: String,
         thread_name: String,
+        board_setting: Option<BoardSetting>,
+        /// Where the thread view should open, from e.g. `/l50` in the URL.
+        read_range: Option<ReadRange>,
     },
     #[serde(rename_all = "camelCase")]
     Stream { stream_url: String },
     #[serde(rename_all = "camelCase")]
+    PeerCastChannel {
+        channel_id: String,
+        tip: Option<String>,
+        stream_url: String,
+    },
+    #[serde(rename_all = "camelCase")]
     Unknown { error: Option<String> },
 }
 
 #[tauri::command]
-pub async fn resolve_url(url: String) -> ResolveUrlResult {
-    let url_type = resolve_url::resolve_url(&url).await;
+pub async fn resolve_url(app_handle: AppHandle, url: String) -> ResolveUrlResult {
+    let peercast_node = settings(&app_handle).get().peercast_node;
+    let url_type = resolve_url::resolve_url(&url, &peercast_node).await;
     match url_type {
         Err(e) => ResolveUrlResult::Unknown {
             error: Some(e.to_string()),
@@ -63,33 +134,311 @@ pub async fn resolve_url(url: String) -> ResolveUrlResult {
             thread_url,
             charset,
             thread_name,
+            board_setting,
+            read_range,
         }) => ResolveUrlResult::Bbs {
             thread_url: thread_url.to_string(),
             charset,
             thread_name,
+            board_setting,
+            read_range,
         },
         Ok(UrlType::Stream { stream_url }) => ResolveUrlResult::Stream { stream_url },
+        Ok(UrlType::PeerCastChannel {
+            channel,
+            stream_url,
+        }) => ResolveUrlResult::PeerCastChannel {
+            channel_id: channel.id,
+            tip: channel.tip,
+            stream_url,
+        },
         Ok(UrlType::Unknown) => ResolveUrlResult::Unknown { error: None },
     }
 }
 
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThreadListItem {
+    thread_url: String,
+    #[serde(flatten)]
+    entry: SubjectEntry,
+}
+
 #[tauri::command]
-pub async fn post(
+pub async fn list_threads(url: String) -> Result<Vec<ThreadListItem>, String> {
+    let url = Url::parse(&url).map_err(|x| x.to_string())?;
+    let bbs_url = bbs::parse_bbs_url(url).map_err(|url| format!("Not a BBS URL: {}", url))?;
+    let threads = bbs::fetch_thread_list(&bbs_url).await.map_err(|x| {
+        error!("{:?}", x);
+        x.to_string()
+    })?;
+    Ok(threads
+        .into_iter()
+        .map(|(thread_url, entry)| ThreadListItem {
+            thread_url: thread_url.to_string(),
+            entry,
+        })
+        .collect())
+}
+
+#[tauri::command]
+pub async fn fetch_posts(
+    app_handle: AppHandle,
     url: String,
     charset: String,
-    name: String,
-    email: String,
-    msg: String,
-) -> Result<(), String> {
+) -> Result<Vec<Post>, String> {
     let url = Url::parse(&url).map_err(|x| x.to_string())?;
-    let bbs = bbs::new(&url).await.map_err(|x| x.to_string())?;
-    bbs.post(&charset, &name, &email, &msg).await.map_err(|x| {
+    lookup_encoding(&charset).map_err(|x| x.to_string())?;
+    let bbs = bbs::new(&url).map_err(|x| x.to_string())?;
+    let posts = bbs.fetch_posts(&charset).await.map_err(|x| {
         error!("{:?}", x);
         x.to_string()
     })?;
+    Ok(ng_filter(&app_handle).apply(posts, &mut HashSet::new()))
+}
+
+fn into_post_error(err: anyhow::Error) -> PostError {
+    err.downcast::<PostError>().unwrap_or_else(|err| {
+        error!("{:?}", err);
+        PostError::new(PostErrorKind::Unknown, err.to_string())
+    })
+}
+
+/// Fills in the name and mail left out by the caller from the settings.
+fn fill_identity(
+    app_handle: &AppHandle,
+    url: &Url,
+    name: Option<String>,
+    email: Option<String>,
+) -> anyhow::Result<(String, String)> {
+    if let (Some(name), Some(email)) = (&name, &email) {
+        return Ok((name.clone(), email.clone()));
+    }
+    let identity = resolve_identity(app_handle, url)?;
+    Ok((
+        name.unwrap_or_else(|| identity.name_field()),
+        email.unwrap_or_else(|| identity.mail_field()),
+    ))
+}
+
+#[tauri::command]
+pub async fn post(
+    app_handle: AppHandle,
+    url: String,
+    charset: String,
+    name: Option<String>,
+    email: Option<String>,
+    msg: String,
+) -> Result<(), PostError> {
+    let url = Url::parse(&url).map_err(|x| into_post_error(x.into()))?;
+    lookup_encoding(&charset).map_err(into_post_error)?;
+    let (name, email) = fill_identity(&app_handle, &url, name, email).map_err(into_post_error)?;
+    post_and_record(&app_handle, &url, &charset, &name, &email, &msg)
+        .await
+        .map_err(into_post_error)?;
+    Ok(())
+}
+
+/// Lists the characters of `msg` that will be sent as numeric character
+/// references, so that the user can be warned before posting.
+#[tauri::command]
+pub fn find_unencodable_chars(charset: String, msg: String) -> Result<Vec<String>, String> {
+    let encoding = lookup_encoding(&charset).map_err(|x| x.to_string())?;
+    Ok(unencodable_chars(encoding, &msg)
+        .into_iter()
+        .map(|x| x.to_string())
+        .collect())
+}
+
+#[tauri::command]
+pub async fn enqueue_post(
+    window: Window,
+    url: String,
+    charset: String,
+    name: Option<String>,
+    email: Option<String>,
+    msg: String,
+) -> Result<String, String> {
+    let parsed_url = Url::parse(&url).map_err(|x| x.to_string())?;
+    lookup_encoding(&charset).map_err(|x| x.to_string())?;
+    let (name, email) =
+        fill_identity(window.app_handle(), &parsed_url, name, email).map_err(|x| x.to_string())?;
+    Ok(post_queue::enqueue(
+        window.app_handle(),
+        window.label().to_owned(),
+        url,
+        charset,
+        name,
+        email,
+        msg,
+    ))
+}
+
+#[tauri::command]
+pub fn get_post_queue(app_handle: AppHandle) -> Vec<PostDraft> {
+    post_queue(&app_handle).drafts()
+}
+
+#[tauri::command]
+pub fn retry_post(window: Window, id: String) {
+    post_queue::retry(window.app_handle(), window.label(), &id);
+}
+
+#[tauri::command]
+pub fn delete_post_draft(app_handle: AppHandle, id: String) {
+    post_queue(&app_handle).remove(&id);
+}
+
+#[tauri::command]
+pub fn query_post_history(
+    app_handle: AppHandle,
+    query: Option<PostHistoryQuery>,
+) -> Vec<PostHistoryEntry> {
+    post_history(&app_handle).query(&query.unwrap_or_default())
+}
+
+#[tauri::command]
+pub fn delete_post_history(app_handle: AppHandle, ids: Vec<String>) {
+    post_history(&app_handle).delete(&ids);
+}
+
+#[tauri::command]
+pub fn get_settings(app_handle: AppHandle) -> Settings {
+    settings(&app_handle).get()
+}
+
+#[tauri::command]
+pub fn set_settings(app_handle: AppHandle, settings: Settings) -> Result<(), String> {
+    self::settings(&app_handle).set(settings).map_err(|x| {
+        error!("{:?}", x);
+        x.to_string()
+    })
+}
+
+/// Lists the channels of every configured YP.
+#[tauri::command]
+pub async fn list_yp_channels(app_handle: AppHandle) -> Vec<YpChannel> {
+    let yp_urls = settings(&app_handle).get().yp_urls;
+    yp::fetch_channels(&yp_urls).await
+}
+
+/// Plays a channel from the YP directory in a new window.
+#[tauri::command]
+pub async fn open_yp_channel(app_handle: AppHandle, channel: YpChannel) -> Result<(), String> {
+    let node = settings(&app_handle).get().peercast_node;
+    let stream_url = channel
+        .channel_url()
+        .stream_url(&node)
+        .map_err(|x| x.to_string())?;
+    let non_empty = |x: String| Some(x).filter(|x| !x.is_empty());
+    let stream_info = StreamInfo {
+        url: Some(stream_url),
+        channel_name: non_empty(channel.name),
+        contact_url: non_empty(channel.contact_url),
+        genre: non_empty(channel.genre),
+        description: non_empty(channel.description),
+        comment: non_empty(channel.comment),
+    };
+    open_player_window(&app_handle, stream_info);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn start_thread_polling(window: Window, url: String, charset: String) -> Result<(), String> {
+    let url = Url::parse(&url).map_err(|x| x.to_string())?;
+    lookup_encoding(&charset).map_err(|x| x.to_string())?;
+    bind_thread(window.app_handle(), window.label(), url, charset);
     Ok(())
 }
 
+#[tauri::command]
+pub fn stop_thread_polling(window: Window) {
+    let state = state(window.app_handle());
+    let mut state = state.lock().unwrap();
+    let state = state.window_state_mut(window.label());
+    state.thread = None;
+    state.thread_poller = None;
+}
+
+/// Called whenever the player is (re)built for `url`.
+#[tauri::command]
+pub fn start_stall_detection(window: Window, url: String) {
+    let detector = StallDetector::start(
+        window.app_handle().to_owned(),
+        window.label().to_owned(),
+        url.clone(),
+    );
+    let state = state(window.app_handle());
+    let mut state = state.lock().unwrap();
+    let state = state.window_state_mut(window.label());
+    // Followed by the channel info refresher.
+    state.stream_info.url = Some(url);
+    state.stall_detector = Some(detector);
+}
+
+#[tauri::command]
+pub fn report_stream_data(window: Window) {
+    let state = state(window.app_handle());
+    let mut state = state.lock().unwrap();
+    let state = state.window_state_mut(window.label());
+    if let Some(detector) = &state.stall_detector {
+        detector.report_data();
+    }
+}
+
+#[tauri::command]
+pub fn report_buffering(window: Window, buffering: bool) {
+    let state = state(window.app_handle());
+    let mut state = state.lock().unwrap();
+    let state = state.window_state_mut(window.label());
+    if let Some(detector) = &state.stall_detector {
+        detector.report_buffering(buffering);
+    }
+}
+
+/// A client for the local node and the ID of the channel played in the window.
+fn channel_client(window: &Window) -> Result<(PeerCastClient, String), String> {
+    let url = {
+        let state = state(window.app_handle());
+        let mut state = state.lock().unwrap();
+        let state = state.window_state_mut(window.label());
+        state.stream_info.url.clone()
+    };
+    let channel = url
+        .and_then(|x| Url::parse(&x).ok())
+        .and_then(|x| parse_channel_url(&x))
+        .ok_or_else(|| "Not a PeerCast channel".to_owned())?;
+    let node = settings(window.app_handle()).get().peercast_node;
+    Ok((PeerCastClient::new(&node), channel.id))
+}
+
+#[tauri::command]
+pub async fn get_channel_status(window: Window) -> Result<ChannelStatus, String> {
+    let (client, channel_id) = channel_client(&window)?;
+    client
+        .get_channel_status(&channel_id)
+        .await
+        .map_err(|x| x.to_string())
+}
+
+#[tauri::command]
+pub async fn get_channel_relay_tree(window: Window) -> Result<Vec<RelayNode>, String> {
+    let (client, channel_id) = channel_client(&window)?;
+    client
+        .get_channel_relay_tree(&channel_id)
+        .await
+        .map_err(|x| x.to_string())
+}
+
+#[tauri::command]
+pub async fn stop_channel(window: Window) -> Result<(), String> {
+    let (client, channel_id) = channel_client(&window)?;
+    client
+        .stop_channel(&channel_id)
+        .await
+        .map_err(|x| x.to_string())
+}
+
 #[tauri::command]
 pub fn resize_video(window: Window, width: u32, height: u32) {
     let state = state(window.app_handle());
@@ -0,0 +1,72 @@
+use std::{env, fs, process::Command};
+
+use anyhow::Result;
+use log::{info, warn};
+use tauri::{AppHandle, Manager};
+
+use crate::peercast::URL_SCHEMES;
+
+const DESKTOP_FILE_NAME: &str = "pcoplayer.desktop";
+
+fn desktop_entry() -> Result<String> {
+    let exe = env::current_exe()?;
+    let mime_types: String = URL_SCHEMES
+        .iter()
+        .map(|x| format!("x-scheme-handler/{};", x))
+        .collect();
+    Ok(format!(
+        "[Desktop Entry]\n\
+         Type=Application\n\
+         Name=pcoplayer\n\
+         Exec=\"{}\" %u\n\
+         Terminal=false\n\
+         NoDisplay=true\n\
+         MimeType={}\n",
+        exe.display(),
+        mime_types
+    ))
+}
+
+/// The desktop file handling `x-scheme-handler/<scheme>`, if any.
+fn default_handler(scheme: &str) -> Result<Option<String>> {
+    let output = Command::new("xdg-mime")
+        .args(["query", "default"])
+        .arg(format!("x-scheme-handler/{}", scheme))
+        .output()?;
+    let handler = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+    Ok(Some(handler).filter(|x| !x.is_empty()))
+}
+
+/// Installs a `.desktop` entry under `$XDG_DATA_HOME/applications` and makes
+/// it the handler of `peercast://` and `pcp://`, unless another application
+/// handles them.
+fn register_url_schemes(app: &AppHandle) -> Result<()> {
+    let dir = app.path().data_dir()?.join("applications");
+    let path = dir.join(DESKTOP_FILE_NAME);
+    let entry = desktop_entry()?;
+    if fs::read_to_string(&path).is_ok_and(|x| x == entry) {
+        return Ok(());
+    }
+    fs::create_dir_all(&dir)?;
+    fs::write(&path, entry)?;
+    for scheme in URL_SCHEMES {
+        match default_handler(scheme)? {
+            Some(handler) if handler != DESKTOP_FILE_NAME => {
+                info!("{}:// is handled by {}", scheme, handler);
+                continue;
+            }
+            _ => {}
+        }
+        Command::new("xdg-mime")
+            .args(["default", DESKTOP_FILE_NAME])
+            .arg(format!("x-scheme-handler/{}", scheme))
+            .status()?;
+    }
+    Ok(())
+}
+
+pub fn setup(app: AppHandle) {
+    if let Err(e) = register_url_schemes(&app) {
+        warn!("Failed to register URL schemes: {}", e);
+    }
+}
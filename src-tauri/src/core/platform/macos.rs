@@ -6,10 +6,9 @@ use objc2::{
 };
 use objc2_app_kit::{NSApplication, NSApplicationDelegate};
 use objc2_foundation::{MainThreadMarker, NSArray, NSObject, NSObjectProtocol, NSURL};
-use tauri::{AppHandle, Manager, WebviewUrl, WebviewWindow, WebviewWindowBuilder};
-use uuid::Uuid;
+use tauri::AppHandle;
 
-use crate::core::state::{state, StreamInfo};
+use crate::core::{state::StreamInfo, window::open_player_window};
 
 fn log_to_file(message: &str) {
     let mut file = OpenOptions::new()
@@ -20,35 +19,22 @@ fn log_to_file(message: &str) {
     file.write_fmt(format_args!("{}\n", message)).unwrap();
 }
 
-fn create_window(app_handle: &AppHandle) -> WebviewWindow {
-    let webview_url = WebviewUrl::App("index.html".into());
-    WebviewWindowBuilder::new(app_handle, Uuid::new_v4().to_string(), webview_url)
-        .title("pcoplayer")
-        .build()
-        .unwrap()
-}
-
 fn application_open_urls(
     _zelf: &AppDelegate,
     _application: &NSApplication,
     urls: &NSArray<NSURL>,
 ) -> bool {
-    let url = urls.to_vec()[0];
-    let url_string = unsafe { url.absoluteString() }.unwrap().to_string();
-
     let app = &PLATFORM.get().unwrap().app;
-    let window = create_window(app);
-
-    let state = state(window.app_handle());
-    let mut state = state.lock().unwrap();
-    state.init_window(
-        &window,
-        StreamInfo {
-            url: Some(url_string),
-            channel_name: None,
-            contact_url: None,
-        },
-    );
+    for url in urls.to_vec() {
+        let url_string = unsafe { url.absoluteString() }.unwrap().to_string();
+        open_player_window(
+            app,
+            StreamInfo {
+                url: Some(url_string),
+                ..Default::default()
+            },
+        );
+    }
     true
 }
 
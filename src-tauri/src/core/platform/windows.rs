@@ -1,3 +1,104 @@
+use std::env;
+
+use anyhow::Result;
+use log::{info, warn};
 use tauri::AppHandle;
+use windows::{
+    core::{HSTRING, PCWSTR},
+    Win32::System::Registry::{
+        RegCloseKey, RegCreateKeyW, RegGetValueW, RegSetValueExW, HKEY, HKEY_CURRENT_USER, REG_SZ,
+        RRF_RT_REG_SZ,
+    },
+};
+
+use crate::peercast::URL_SCHEMES;
+
+fn set_string(key: HKEY, name: Option<&str>, value: &str) -> Result<()> {
+    let data: Vec<u8> = value
+        .encode_utf16()
+        .chain([0])
+        .flat_map(|x| x.to_le_bytes())
+        .collect();
+    let name = name.map(HSTRING::from);
+    let name = name.as_ref().map_or(PCWSTR::null(), |x| PCWSTR(x.as_ptr()));
+    unsafe { RegSetValueExW(key, name, 0, REG_SZ, Some(&data)) }.ok()?;
+    Ok(())
+}
+
+/// Reads the default value of `HKCU\<path>`, if the key exists.
+fn read_default(path: &str) -> Option<String> {
+    let path = HSTRING::from(path);
+    let mut size = 0u32;
+    unsafe {
+        RegGetValueW(
+            HKEY_CURRENT_USER,
+            &path,
+            PCWSTR::null(),
+            RRF_RT_REG_SZ,
+            None,
+            None,
+            Some(&mut size),
+        )
+    }
+    .ok()
+    .ok()?;
+    let mut buf = vec![0u16; (size as usize).div_ceil(2)];
+    unsafe {
+        RegGetValueW(
+            HKEY_CURRENT_USER,
+            &path,
+            PCWSTR::null(),
+            RRF_RT_REG_SZ,
+            None,
+            Some(buf.as_mut_ptr().cast()),
+            Some(&mut size),
+        )
+    }
+    .ok()
+    .ok()?;
+    let len = buf.iter().position(|&x| x == 0).unwrap_or(buf.len());
+    Some(String::from_utf16_lossy(&buf[..len]))
+}
+
+/// Writes `HKCU\Software\Classes\<path>` with its default value and other values.
+fn write_key(path: &str, default: &str, values: &[(&str, &str)]) -> Result<()> {
+    let mut key = HKEY::default();
+    unsafe { RegCreateKeyW(HKEY_CURRENT_USER, &HSTRING::from(path), &mut key) }.ok()?;
+    let result = set_string(key, None, default).and_then(|_| {
+        values
+            .iter()
+            .try_for_each(|(name, value)| set_string(key, Some(name), value))
+    });
+    let _ = unsafe { RegCloseKey(key) };
+    result
+}
+
+/// Opens `peercast://` and `pcp://` URLs with this executable for the current
+/// user, unless another application such as PeerCastStation handles them.
+fn register_url_schemes() -> Result<()> {
+    let exe = env::current_exe()?;
+    let command = format!("\"{}\" \"%1\"", exe.display());
+    let exe = exe.to_string_lossy().to_lowercase();
+    for scheme in URL_SCHEMES {
+        let path = format!(r"Software\Classes\{}", scheme);
+        if let Some(current) = read_default(&format!(r"{}\shell\open\command", path)) {
+            if !current.to_lowercase().contains(&exe) {
+                info!("{}:// is handled by {}", scheme, current);
+                continue;
+            }
+        }
+        write_key(
+            &path,
+            &format!("URL:{} Protocol", scheme),
+            &[("URL Protocol", "")],
+        )?;
+        write_key(&format!(r"{}\shell\open\command", path), &command, &[])?;
+    }
+    Ok(())
+}
 
-pub fn setup(_app: AppHandle) {}
+pub fn setup(_app: AppHandle) {
+    if let Err(e) = register_url_schemes() {
+        warn!("Failed to register URL schemes: {}", e);
+    }
+}
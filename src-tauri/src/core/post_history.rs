@@ -0,0 +1,205 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::PathBuf,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use log::warn;
+use tauri::{App, AppHandle, Manager, Runtime, State};
+use url::Url;
+use uuid::Uuid;
+
+use crate::bbs::{self, Post, PostError};
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PostHistoryEntry {
+    pub id: String,
+    pub thread_url: String,
+    pub board_url: String,
+    /// Milliseconds since the Unix epoch.
+    pub posted_at: u64,
+    pub name: String,
+    pub mail: String,
+    pub body: String,
+    /// `None` when the post succeeded.
+    pub error: Option<PostError>,
+    /// Filled in when the post is found in the thread.
+    pub post_number: Option<u32>,
+}
+
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PostHistoryQuery {
+    pub thread_url: Option<String>,
+    pub board_url: Option<String>,
+    pub text: Option<String>,
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+}
+
+impl PostHistoryQuery {
+    fn matches(&self, entry: &PostHistoryEntry) -> bool {
+        self.thread_url
+            .as_ref()
+            .is_none_or(|x| *x == entry.thread_url)
+            && self
+                .board_url
+                .as_ref()
+                .is_none_or(|x| *x == entry.board_url)
+            && self
+                .text
+                .as_ref()
+                .is_none_or(|x| entry.body.contains(x.as_str()))
+            && self.since.is_none_or(|x| entry.posted_at >= x)
+            && self.until.is_none_or(|x| entry.posted_at < x)
+    }
+}
+
+/// Allowed difference between the local clock and the board's.
+const CLOCK_SKEW_MILLIS: u64 = 60 * 1000;
+
+fn normalize_body(body: &str) -> String {
+    body.replace("\r\n", "\n").trim().to_owned()
+}
+
+pub struct PostHistory {
+    path: PathBuf,
+    entries: Mutex<Vec<PostHistoryEntry>>,
+}
+
+impl PostHistory {
+    pub fn load(path: PathBuf) -> Self {
+        let entries = match fs::read(&path) {
+            Ok(json) => serde_json::from_slice(&json).unwrap_or_else(|e| {
+                warn!("Failed to parse post history: {}", e);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        Self {
+            path,
+            entries: Mutex::new(entries),
+        }
+    }
+
+    fn save(&self, entries: &[PostHistoryEntry]) {
+        let result = self
+            .path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&self.path, serde_json::to_vec(entries).unwrap()));
+        if let Err(e) = result {
+            warn!("Failed to save post history: {}", e);
+        }
+    }
+
+    fn update<T>(&self, f: impl FnOnce(&mut Vec<PostHistoryEntry>) -> T) -> T {
+        let mut entries = self.entries.lock().unwrap();
+        let result = f(&mut entries);
+        self.save(&entries);
+        result
+    }
+
+    fn push(&self, entry: PostHistoryEntry) {
+        self.update(|entries| entries.push(entry));
+    }
+
+    /// Newest first.
+    pub fn query(&self, query: &PostHistoryQuery) -> Vec<PostHistoryEntry> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .iter()
+            .rev()
+            .filter(|x| query.matches(x))
+            .cloned()
+            .collect()
+    }
+
+    pub fn delete(&self, ids: &[String]) {
+        self.update(|entries| entries.retain(|x| !ids.contains(&x.id)));
+    }
+
+    pub fn own_post_numbers(&self, thread_url: &str) -> HashSet<u32> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .iter()
+            .filter(|x| x.thread_url == thread_url)
+            .filter_map(|x| x.post_number)
+            .collect()
+    }
+
+    /// Matches newly read posts of `thread_url` with successful posts awaiting
+    /// their number. Only posts dated after the submission can match, since
+    /// short comments like "ｗ" are often written by others too.
+    pub fn resolve_post_numbers(&self, thread_url: &str, posts: &[Post]) {
+        let mut entries = self.entries.lock().unwrap();
+        let mut changed = false;
+        for post in posts {
+            let Some(post_posted_at) = post.posted_at() else {
+                continue;
+            };
+            let body = normalize_body(&post.body);
+            if let Some(entry) = entries.iter_mut().find(|x| {
+                x.thread_url == thread_url
+                    && x.error.is_none()
+                    && x.post_number.is_none()
+                    && post_posted_at + CLOCK_SKEW_MILLIS >= x.posted_at
+                    && normalize_body(&x.body) == body
+            }) {
+                entry.post_number = Some(post.number);
+                changed = true;
+            }
+        }
+        if changed {
+            self.save(&entries);
+        }
+    }
+}
+
+pub fn init_post_history(app: &App) -> tauri::Result<()> {
+    let path = app.path().app_data_dir()?.join("post_history.json");
+    app.manage(PostHistory::load(path));
+    Ok(())
+}
+
+pub fn post_history(app_handle: &AppHandle<impl Runtime>) -> State<'_, PostHistory> {
+    app_handle.state::<PostHistory>()
+}
+
+/// Posts to the thread and records the attempt in the history.
+pub async fn post_and_record(
+    app_handle: &AppHandle<impl Runtime>,
+    thread_url: &Url,
+    charset: &str,
+    name: &str,
+    email: &str,
+    msg: &str,
+) -> Result<()> {
+    let thread = bbs::new(thread_url)?;
+    let result = thread.post(charset, name, email, msg).await;
+    let error = match &result {
+        Ok(()) => None,
+        Err(e) => match e.downcast_ref::<PostError>() {
+            Some(e) => Some(e.clone()),
+            // Not answered by the server; nothing was posted.
+            None => return result,
+        },
+    };
+    let posted_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+    post_history(app_handle).push(PostHistoryEntry {
+        id: Uuid::new_v4().to_string(),
+        thread_url: thread.thread_url(thread.key()).to_string(),
+        board_url: thread.board_url().to_string(),
+        posted_at,
+        name: name.to_owned(),
+        mail: email.to_owned(),
+        body: msg.to_owned(),
+        error,
+        post_number: None,
+    });
+    result
+}
@@ -0,0 +1,388 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use log::{error, warn};
+use tauri::{App, AppHandle, Emitter, Manager, Runtime, State};
+use url::Url;
+use uuid::Uuid;
+
+use crate::bbs::{self, PostError, PostErrorKind};
+
+use super::post_history::post_and_record;
+
+/// Used when the board doesn't publish its interval.
+const DEFAULT_POST_INTERVAL: Duration = Duration::from_secs(10);
+const MAX_POST_INTERVAL: Duration = Duration::from_secs(120);
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(5);
+const MAX_ATTEMPTS: u32 = 5;
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum DraftStatus {
+    /// Waiting for its turn to be sent.
+    Queued,
+    Sending,
+    Failed {
+        error: PostError,
+    },
+    /// Left over from a previous session; sent only when retried.
+    Draft,
+    /// The connection failed after the request was sent, so the post may
+    /// have gone through. Sent again only when retried.
+    Unconfirmed {
+        error: PostError,
+    },
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PostDraft {
+    pub id: String,
+    pub thread_url: String,
+    pub charset: String,
+    pub name: String,
+    pub email: String,
+    pub msg: String,
+    pub attempts: u32,
+    pub status: DraftStatus,
+    /// Window that queued or retried the draft and is told its result.
+    #[serde(default)]
+    pub window_label: Option<String>,
+}
+
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PostResultPayload {
+    id: String,
+    thread_url: String,
+    error: Option<PostError>,
+}
+
+#[derive(Default)]
+struct Inner {
+    drafts: Vec<PostDraft>,
+    workers: HashSet<String>,
+    intervals: HashMap<String, Duration>,
+    last_posted: HashMap<String, Instant>,
+}
+
+pub struct PostQueue {
+    path: PathBuf,
+    inner: Mutex<Inner>,
+}
+
+impl PostQueue {
+    pub fn load(path: PathBuf) -> Self {
+        let drafts = match fs::read(&path) {
+            Ok(json) => serde_json::from_slice::<Vec<PostDraft>>(&json).unwrap_or_else(|e| {
+                warn!("Failed to parse drafts: {}", e);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        let drafts = drafts
+            .into_iter()
+            .map(|mut draft| {
+                if matches!(draft.status, DraftStatus::Queued | DraftStatus::Sending) {
+                    draft.status = DraftStatus::Draft;
+                }
+                draft
+            })
+            .collect();
+        Self {
+            path,
+            inner: Mutex::new(Inner {
+                drafts,
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn save(&self, drafts: &[PostDraft]) {
+        let result = self
+            .path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&self.path, serde_json::to_vec(drafts).unwrap()));
+        if let Err(e) = result {
+            warn!("Failed to save drafts: {}", e);
+        }
+    }
+
+    pub fn drafts(&self) -> Vec<PostDraft> {
+        self.inner.lock().unwrap().drafts.clone()
+    }
+
+    fn update<T>(&self, f: impl FnOnce(&mut Inner) -> T) -> T {
+        let mut inner = self.inner.lock().unwrap();
+        let result = f(&mut inner);
+        self.save(&inner.drafts);
+        result
+    }
+
+    fn set_status(&self, id: &str, status: DraftStatus) {
+        self.update(|inner| {
+            if let Some(draft) = inner.drafts.iter_mut().find(|x| x.id == id) {
+                draft.status = status;
+            }
+        });
+    }
+
+    /// Sets the interval of the board unless it is known already.
+    fn init_interval(&self, thread_url: &str, interval: Duration) {
+        let mut inner = self.inner.lock().unwrap();
+        inner
+            .intervals
+            .entry(thread_url.to_owned())
+            .or_insert(interval);
+    }
+
+    /// Returns whether a new worker has to be started for the thread.
+    fn push(&self, draft: PostDraft) -> bool {
+        self.update(|inner| {
+            let thread_url = draft.thread_url.clone();
+            inner.drafts.push(draft);
+            inner.workers.insert(thread_url)
+        })
+    }
+
+    /// Returns whether a new worker has to be started for the thread.
+    fn requeue(&self, id: &str, window_label: &str) -> Option<(String, bool)> {
+        self.update(|inner| {
+            let draft = inner.drafts.iter_mut().find(|x| x.id == id)?;
+            draft.status = DraftStatus::Queued;
+            draft.attempts = 0;
+            draft.window_label = Some(window_label.to_owned());
+            let thread_url = draft.thread_url.clone();
+            let start = inner.workers.insert(thread_url.clone());
+            Some((thread_url, start))
+        })
+    }
+
+    pub fn remove(&self, id: &str) {
+        self.update(|inner| inner.drafts.retain(|x| x.id != id));
+    }
+
+    /// Takes the next queued draft and how long to wait before sending it.
+    /// Unregisters the worker when the queue of the thread is empty.
+    fn next(&self, thread_url: &str) -> Option<(PostDraft, Duration)> {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(draft) = inner
+            .drafts
+            .iter()
+            .find(|x| x.thread_url == thread_url && matches!(x.status, DraftStatus::Queued))
+            .cloned()
+        else {
+            inner.workers.remove(thread_url);
+            return None;
+        };
+        let interval = *inner
+            .intervals
+            .get(thread_url)
+            .unwrap_or(&DEFAULT_POST_INTERVAL);
+        let wait = inner
+            .last_posted
+            .get(thread_url)
+            .map_or(Duration::ZERO, |x| interval.saturating_sub(x.elapsed()));
+        Some((draft, wait))
+    }
+
+    fn complete(&self, draft: &PostDraft) {
+        self.update(|inner| {
+            inner.drafts.retain(|x| x.id != draft.id);
+            inner
+                .last_posted
+                .insert(draft.thread_url.clone(), Instant::now());
+        });
+    }
+
+    fn retry_later(&self, draft: &PostDraft, rate_limited: bool) {
+        self.update(|inner| {
+            if let Some(x) = inner.drafts.iter_mut().find(|x| x.id == draft.id) {
+                x.status = DraftStatus::Queued;
+                x.attempts += 1;
+            }
+            if rate_limited {
+                let interval = inner
+                    .intervals
+                    .entry(draft.thread_url.clone())
+                    .or_insert(DEFAULT_POST_INTERVAL);
+                *interval = (*interval * 2).min(MAX_POST_INTERVAL).max(*interval);
+                inner
+                    .last_posted
+                    .insert(draft.thread_url.clone(), Instant::now());
+            }
+        });
+    }
+}
+
+pub fn init_post_queue(app: &App) -> tauri::Result<()> {
+    let path = app.path().app_data_dir()?.join("drafts.json");
+    app.manage(PostQueue::load(path));
+    Ok(())
+}
+
+pub fn post_queue(app_handle: &AppHandle<impl Runtime>) -> State<'_, PostQueue> {
+    app_handle.state::<PostQueue>()
+}
+
+fn emit_queue(app_handle: &AppHandle) {
+    let drafts = post_queue(app_handle).drafts();
+    if let Err(e) = app_handle.emit("post-queue", drafts) {
+        warn!("Failed to emit post-queue: {}", e);
+    }
+}
+
+fn emit_result(app_handle: &AppHandle, draft: &PostDraft, error: Option<PostError>) {
+    let Some(label) = &draft.window_label else {
+        return;
+    };
+    let payload = PostResultPayload {
+        id: draft.id.clone(),
+        thread_url: draft.thread_url.clone(),
+        error,
+    };
+    if let Err(e) = app_handle.emit_to(label, "post-result", payload) {
+        warn!("Failed to emit post-result: {}", e);
+    }
+}
+
+/// Failures that left the post unsent.
+fn is_transient(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<reqwest::Error>()
+        .is_some_and(|x| x.is_connect() || x.status().is_some_and(|x| x.is_server_error()))
+}
+
+/// Failures after the request may have reached the board, e.g. timeouts.
+fn is_unconfirmed(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<reqwest::Error>()
+        .is_some_and(|x| !x.is_connect() && !x.is_builder() && x.status().is_none())
+}
+
+async fn fetch_post_interval(thread_url: &str) -> Result<Option<Duration>> {
+    let thread = bbs::new(&Url::parse(thread_url)?)?;
+    let setting = thread.fetch_setting().await?;
+    Ok(setting.post_interval.map(|x| Duration::from_secs(x as u64)))
+}
+
+async fn send(app_handle: &AppHandle, draft: &PostDraft) -> Result<()> {
+    let url = Url::parse(&draft.thread_url)?;
+    post_and_record(
+        app_handle,
+        &url,
+        &draft.charset,
+        &draft.name,
+        &draft.email,
+        &draft.msg,
+    )
+    .await
+}
+
+async fn run_worker(app_handle: AppHandle, thread_url: String) {
+    match fetch_post_interval(&thread_url).await {
+        Ok(Some(interval)) => post_queue(&app_handle).init_interval(&thread_url, interval),
+        Ok(None) => {}
+        Err(e) => warn!("Failed to fetch board setting: {}", e),
+    }
+    let mut retry_delay = INITIAL_RETRY_DELAY;
+    loop {
+        let Some((draft, wait)) = post_queue(&app_handle).next(&thread_url) else {
+            return;
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+            // The draft may have been removed in the meantime.
+            continue;
+        }
+        post_queue(&app_handle).set_status(&draft.id, DraftStatus::Sending);
+        emit_queue(&app_handle);
+        match send(&app_handle, &draft).await {
+            Ok(()) => {
+                retry_delay = INITIAL_RETRY_DELAY;
+                post_queue(&app_handle).complete(&draft);
+                emit_result(&app_handle, &draft, None);
+            }
+            Err(e) if is_transient(&e) && draft.attempts + 1 < MAX_ATTEMPTS => {
+                warn!("Failed to post, retrying: {}", e);
+                post_queue(&app_handle).retry_later(&draft, false);
+                emit_queue(&app_handle);
+                tokio::time::sleep(retry_delay).await;
+                retry_delay *= 2;
+            }
+            Err(e) if is_unconfirmed(&e) => {
+                warn!("Post may have been sent: {}", e);
+                let error = PostError::new(PostErrorKind::Unknown, e.to_string());
+                post_queue(&app_handle).set_status(
+                    &draft.id,
+                    DraftStatus::Unconfirmed {
+                        error: error.clone(),
+                    },
+                );
+                emit_result(&app_handle, &draft, Some(error));
+            }
+            Err(e) => {
+                let error = e.downcast::<PostError>().unwrap_or_else(|e| {
+                    error!("{:?}", e);
+                    PostError::new(PostErrorKind::Unknown, e.to_string())
+                });
+                if error.kind == PostErrorKind::RateLimited && draft.attempts + 1 < MAX_ATTEMPTS {
+                    post_queue(&app_handle).retry_later(&draft, true);
+                } else {
+                    post_queue(&app_handle).set_status(
+                        &draft.id,
+                        DraftStatus::Failed {
+                            error: error.clone(),
+                        },
+                    );
+                    emit_result(&app_handle, &draft, Some(error));
+                }
+            }
+        }
+        emit_queue(&app_handle);
+    }
+}
+
+fn start_worker(app_handle: &AppHandle, thread_url: String) {
+    tauri::async_runtime::spawn(run_worker(app_handle.to_owned(), thread_url));
+}
+
+pub fn enqueue(
+    app_handle: &AppHandle,
+    window_label: String,
+    thread_url: String,
+    charset: String,
+    name: String,
+    email: String,
+    msg: String,
+) -> String {
+    let id = Uuid::new_v4().to_string();
+    let draft = PostDraft {
+        id: id.clone(),
+        thread_url: thread_url.clone(),
+        charset,
+        name,
+        email,
+        msg,
+        attempts: 0,
+        status: DraftStatus::Queued,
+        window_label: Some(window_label),
+    };
+    if post_queue(app_handle).push(draft) {
+        start_worker(app_handle, thread_url);
+    }
+    emit_queue(app_handle);
+    id
+}
+
+pub fn retry(app_handle: &AppHandle, window_label: &str, id: &str) {
+    if let Some((thread_url, true)) = post_queue(app_handle).requeue(id, window_label) {
+        start_worker(app_handle, thread_url);
+    }
+    emit_queue(app_handle);
+}
@@ -4,23 +4,10 @@ use m3u::Entry;
 use reqwest::header::CONTENT_TYPE;
 use url::Url;
 
-use crate::bbs::{fetch_thread_url_encoding_name, parse_bbs_url};
-
-async fn _check_subject(url: &Url) -> Result<bool> {
-    let origin = url.origin().ascii_serialization();
-    let resp = reqwest::Client::new()
-        .head(format!("{}/subject.txt", origin))
-        .send()
-        .await?
-        .error_for_status()?;
-    Ok(matches!(
-        resp.headers()
-            .get(CONTENT_TYPE)
-            .and_then(|x| x.to_str().ok())
-            .map(|x| x.starts_with("text/plain")),
-        Some(true)
-    ))
-}
+use crate::{
+    bbs::{self, fetch_thread_url_encoding_name, parse_bbs_url, BoardSetting, ReadRange},
+    peercast::{parse_channel_url, ChannelUrl},
+};
 
 fn is_probably_pls(path: &str) -> bool {
     if path.starts_with("/pls/") {
@@ -66,20 +53,43 @@ async fn check_stream(url: &str) -> Result<bool> {
         .map_or(false, |x| x == "video/x-flv"))
 }
 
+async fn fetch_board_setting(thread_url: &Url) -> Option<BoardSetting> {
+    let thread = bbs::new(thread_url).ok()?;
+    thread
+        .fetch_setting()
+        .await
+        .inspect_err(|e| warn!("Failed to fetch board setting: {}", e))
+        .ok()
+}
+
 pub enum UrlType {
     Bbs {
         thread_url: Url,
         charset: String,
         thread_name: String,
+        board_setting: Option<BoardSetting>,
+        read_range: Option<ReadRange>,
     },
     Stream {
         stream_url: String,
     },
+    PeerCastChannel {
+        channel: ChannelUrl,
+        /// The channel on the local node.
+        stream_url: String,
+    },
     Unknown,
 }
 
-pub async fn resolve_url(url_str: &str) -> Result<UrlType> {
+/// `peercast_node` is the `host:port` that PeerCast channels are played through.
+pub async fn resolve_url(url_str: &str, peercast_node: &str) -> Result<UrlType> {
     let url = Url::parse(url_str)?;
+    if let Some(channel) = parse_channel_url(&url) {
+        return Ok(UrlType::PeerCastChannel {
+            stream_url: channel.stream_url(peercast_node)?,
+            channel,
+        });
+    }
     if !matches!(url.scheme(), "http" | "https") {
         bail!("Invalid URL scheme: {}", url.scheme())
     }
@@ -87,10 +97,13 @@ pub async fn resolve_url(url_str: &str) -> Result<UrlType> {
         Err(url) => url,
         Ok(bbs_url) => match fetch_thread_url_encoding_name(&bbs_url).await {
             Ok((thread_url, encoding, thread_name)) => {
+                let board_setting = fetch_board_setting(&thread_url).await;
                 return Ok(UrlType::Bbs {
                     thread_url,
                     charset: encoding,
                     thread_name,
+                    board_setting,
+                    read_range: bbs_url.read_range(),
                 });
             }
             Err(e) => {
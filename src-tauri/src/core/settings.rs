@@ -0,0 +1,158 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use log::warn;
+use tauri::{App, AppHandle, Manager, Runtime, State};
+use url::Url;
+
+use crate::{
+    bbs::{self, NgFilter, NgRule},
+    peercast, yp,
+};
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PostingIdentity {
+    pub name: Option<String>,
+    /// Trip key, sent as `name#trip`.
+    pub trip: Option<String>,
+    pub mail: Option<String>,
+    /// Sends "sage" instead of `mail` when enabled.
+    pub sage: Option<bool>,
+}
+
+impl PostingIdentity {
+    fn overridden_by(&self, other: &PostingIdentity) -> PostingIdentity {
+        PostingIdentity {
+            name: other.name.clone().or_else(|| self.name.clone()),
+            trip: other.trip.clone().or_else(|| self.trip.clone()),
+            mail: other.mail.clone().or_else(|| self.mail.clone()),
+            sage: other.sage.or(self.sage),
+        }
+    }
+
+    pub fn name_field(&self) -> String {
+        let name = self.name.clone().unwrap_or_default();
+        match self.trip.as_deref() {
+            Some(trip) if !trip.is_empty() => format!("{}#{}", name, trip),
+            _ => name,
+        }
+    }
+
+    pub fn mail_field(&self) -> String {
+        if self.sage.unwrap_or(false) {
+            return "sage".to_owned();
+        }
+        self.mail.clone().unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Settings {
+    pub identity: PostingIdentity,
+    /// Keyed by board URL.
+    pub board_identities: HashMap<String, PostingIdentity>,
+    /// Keyed by thread URL.
+    pub thread_identities: HashMap<String, PostingIdentity>,
+    pub ng_rules: Vec<NgRule>,
+    /// `host:port` of the local PeerCast node.
+    pub peercast_node: String,
+    /// `index.txt` of the YPs merged into the channel directory.
+    pub yp_urls: Vec<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            identity: PostingIdentity {
+                sage: Some(true),
+                ..Default::default()
+            },
+            board_identities: HashMap::new(),
+            thread_identities: HashMap::new(),
+            ng_rules: Vec::new(),
+            peercast_node: peercast::DEFAULT_NODE.to_owned(),
+            yp_urls: yp::DEFAULT_YP_URLS.iter().map(|&x| x.to_owned()).collect(),
+        }
+    }
+}
+
+pub struct SettingsStore {
+    path: PathBuf,
+    settings: Mutex<Settings>,
+}
+
+impl SettingsStore {
+    pub fn load(path: PathBuf) -> Self {
+        let settings = match fs::read(&path) {
+            Ok(json) => serde_json::from_slice(&json).unwrap_or_else(|e| {
+                warn!("Failed to parse settings: {}", e);
+                Settings::default()
+            }),
+            Err(_) => Settings::default(),
+        };
+        Self {
+            path,
+            settings: Mutex::new(settings),
+        }
+    }
+
+    pub fn get(&self) -> Settings {
+        self.settings.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, settings: Settings) -> Result<()> {
+        peercast::parse_node(&settings.peercast_node)?;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, serde_json::to_vec_pretty(&settings)?)?;
+        *self.settings.lock().unwrap() = settings;
+        Ok(())
+    }
+}
+
+pub fn init_settings(app: &App) -> tauri::Result<()> {
+    let path = app.path().app_config_dir()?.join("settings.json");
+    app.manage(SettingsStore::load(path));
+    Ok(())
+}
+
+pub fn settings(app_handle: &AppHandle<impl Runtime>) -> State<'_, SettingsStore> {
+    app_handle.state::<SettingsStore>()
+}
+
+/// Merges the default identity with the board and thread overrides.
+pub fn resolve_identity(
+    app_handle: &AppHandle<impl Runtime>,
+    thread_url: &Url,
+) -> Result<PostingIdentity> {
+    let thread = bbs::new(thread_url)?;
+    let settings = settings(app_handle).get();
+    let mut identity = settings.identity.clone();
+    let board_url = thread.board_url();
+    if let Some(board) = settings.board_identities.get(board_url.as_str()) {
+        identity = identity.overridden_by(board);
+    }
+    let thread_url = thread.thread_url(thread.key());
+    if let Some(thread) = settings.thread_identities.get(thread_url.as_str()) {
+        identity = identity.overridden_by(thread);
+    }
+    Ok(identity)
+}
+
+/// Builds the filter from the NG rules that have not expired yet.
+pub fn ng_filter(app_handle: &AppHandle<impl Runtime>) -> NgFilter {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    NgFilter::new(&settings(app_handle).get().ng_rules, now)
+}
@@ -0,0 +1,157 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use log::{info, warn};
+use tauri::{async_runtime::JoinHandle, AppHandle, Emitter, Manager};
+use url::Url;
+
+use crate::peercast::{parse_channel_url, PeerCastClient};
+
+use super::settings::settings;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// How long the stream may deliver nothing before it counts as stalled.
+const NO_DATA_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long the video may keep buffering before it counts as stalled.
+const BUFFERING_TIMEOUT: Duration = Duration::from_secs(5);
+/// Minimum time between two reconnects, so that a dead channel isn't hammered.
+const COOLDOWN: Duration = Duration::from_secs(30);
+
+#[derive(Clone, serde::Serialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+enum StreamStatusPayload {
+    /// The player should be rebuilt. `bumped` tells whether the node was asked
+    /// to find another source first.
+    Reconnecting {
+        bumped: bool,
+    },
+    Playing,
+}
+
+struct Activity {
+    last_data: Instant,
+    buffering_since: Option<Instant>,
+    reconnecting: bool,
+}
+
+impl Activity {
+    fn stalled(&self, now: Instant) -> bool {
+        now - self.last_data >= NO_DATA_TIMEOUT
+            || self
+                .buffering_since
+                .is_some_and(|x| now - x >= BUFFERING_TIMEOUT)
+    }
+}
+
+fn emit(app_handle: &AppHandle, label: &str, payload: StreamStatusPayload) {
+    if let Err(e) = app_handle.emit_to(label, "stream-status", payload) {
+        warn!("Failed to emit stream-status: {}", e);
+    }
+}
+
+/// Asks the local node to reconnect the channel. `false` for other streams.
+async fn bump(app_handle: &AppHandle, stream_url: &str) -> bool {
+    let Some(channel) = Url::parse(stream_url)
+        .ok()
+        .and_then(|x| parse_channel_url(&x))
+    else {
+        return false;
+    };
+    let node = settings(app_handle).get().peercast_node;
+    match PeerCastClient::new(&node).bump_channel(&channel.id).await {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("bumpChannel failed: {:?}", e);
+            false
+        }
+    }
+}
+
+async fn run(
+    app_handle: AppHandle,
+    label: String,
+    stream_url: String,
+    activity: Arc<Mutex<Activity>>,
+) {
+    let mut last_reconnect: Option<Instant> = None;
+    while app_handle.get_webview_window(&label).is_some() {
+        tokio::time::sleep(CHECK_INTERVAL).await;
+        let now = Instant::now();
+        let stalled = activity.lock().unwrap().stalled(now);
+        if !stalled || last_reconnect.is_some_and(|x| now - x < COOLDOWN) {
+            continue;
+        }
+        info!("Stream stalled: {}", stream_url);
+        last_reconnect = Some(now);
+        let bumped = bump(&app_handle, &stream_url).await;
+        {
+            // The rebuilt player gets the full timeouts again.
+            let mut activity = activity.lock().unwrap();
+            activity.last_data = Instant::now();
+            activity.buffering_since = None;
+            activity.reconnecting = true;
+        }
+        emit(
+            &app_handle,
+            &label,
+            StreamStatusPayload::Reconnecting { bumped },
+        );
+    }
+}
+
+/// Watches the data and buffering reports of a window's player and reconnects
+/// the stream when they stop.
+pub struct StallDetector {
+    task: JoinHandle<()>,
+    activity: Arc<Mutex<Activity>>,
+    app_handle: AppHandle,
+    label: String,
+}
+
+impl StallDetector {
+    pub fn start(app_handle: AppHandle, label: String, stream_url: String) -> Self {
+        let activity = Arc::new(Mutex::new(Activity {
+            last_data: Instant::now(),
+            buffering_since: None,
+            reconnecting: false,
+        }));
+        let task = tauri::async_runtime::spawn(run(
+            app_handle.clone(),
+            label.clone(),
+            stream_url,
+            activity.clone(),
+        ));
+        Self {
+            task,
+            activity,
+            app_handle,
+            label,
+        }
+    }
+
+    pub fn report_data(&self) {
+        let mut activity = self.activity.lock().unwrap();
+        activity.last_data = Instant::now();
+        if activity.reconnecting {
+            activity.reconnecting = false;
+            emit(&self.app_handle, &self.label, StreamStatusPayload::Playing);
+        }
+    }
+
+    pub fn report_buffering(&self, buffering: bool) {
+        let mut activity = self.activity.lock().unwrap();
+        if buffering {
+            activity.buffering_since.get_or_insert_with(Instant::now);
+        } else {
+            activity.buffering_since = None;
+        }
+    }
+}
+
+impl Drop for StallDetector {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
@@ -2,19 +2,52 @@ use std::{collections::HashMap, sync::Mutex};
 
 use tauri::{App, AppHandle, Manager, Runtime, State, WebviewWindow};
 
-use crate::core::window::resizer::Resizer;
+use crate::{
+    core::{
+        channel_refresher::ChannelRefresher, stall_detector::StallDetector,
+        thread_poller::ThreadPoller, window::resizer::Resizer,
+    },
+    peercast::ChannelDetails,
+};
 
-#[derive(Clone, Debug, clap::Args, serde::Serialize)]
+#[derive(Clone, Debug, Default, PartialEq, clap::Args, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct StreamInfo {
     pub url: Option<String>,
     pub channel_name: Option<String>,
     pub contact_url: Option<String>,
+    #[arg(skip)]
+    pub genre: Option<String>,
+    #[arg(skip)]
+    pub description: Option<String>,
+    #[arg(skip)]
+    pub comment: Option<String>,
+}
+
+impl StreamInfo {
+    /// Overwrites the fields the node knows, keeping the rest.
+    pub fn apply_channel_info(&mut self, info: &ChannelDetails) {
+        let non_empty = |x: &str| Some(x.to_owned()).filter(|x| !x.is_empty());
+        self.channel_name = non_empty(&info.name).or(self.channel_name.take());
+        self.contact_url = non_empty(&info.url).or(self.contact_url.take());
+        self.genre = non_empty(&info.genre).or(self.genre.take());
+        self.description = non_empty(&info.desc).or(self.description.take());
+        self.comment = non_empty(&info.comment).or(self.comment.take());
+    }
+}
+
+pub struct ThreadBinding {
+    pub thread_url: String,
+    pub charset: String,
 }
 
 pub struct WindowState {
     pub resizer: Resizer,
     pub stream_info: StreamInfo,
+    pub thread: Option<ThreadBinding>,
+    pub thread_poller: Option<ThreadPoller>,
+    pub stall_detector: Option<StallDetector>,
+    pub channel_refresher: Option<ChannelRefresher>,
 }
 
 pub struct AppState {
@@ -32,15 +65,27 @@ impl AppState {
         self.window_states.get_mut(label).unwrap()
     }
 
+    pub fn try_window_state_mut(&mut self, label: &str) -> Option<&mut WindowState> {
+        self.window_states.get_mut(label)
+    }
+
     pub fn init_window(&mut self, window: &WebviewWindow, stream_info: StreamInfo) {
         self.window_states.insert(
             window.label().to_owned(),
             WindowState {
                 resizer: Resizer::new(window),
                 stream_info,
+                thread: None,
+                thread_poller: None,
+                stall_detector: None,
+                channel_refresher: None,
             },
         );
     }
+
+    pub fn remove_window(&mut self, label: &str) {
+        self.window_states.remove(label);
+    }
 }
 
 pub fn init_state(app: &App, stream_info: StreamInfo) {
@@ -0,0 +1,248 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use log::{info, warn};
+use tauri::{async_runtime::JoinHandle, AppHandle, Emitter, Manager};
+use url::Url;
+
+use crate::bbs::{
+    self, find_replies, Post, ReadCursor, ReplyTree, Thread, ThreadNotFound, DEFAULT_THREAD_STOP,
+};
+
+use super::{
+    post_history::post_history,
+    settings::ng_filter,
+    state::{state, ThreadBinding},
+};
+
+const MIN_INTERVAL: Duration = Duration::from_secs(5);
+const MAX_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ThreadPostsPayload {
+    thread_url: String,
+    posts: Vec<Post>,
+    /// Updated "replied by" lists of earlier posts.
+    replied_by: HashMap<u32, Vec<u32>>,
+}
+
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ThreadRepliesPayload {
+    thread_url: String,
+    posts: Vec<Post>,
+}
+
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ThreadChangedPayload {
+    thread_url: String,
+    charset: String,
+    thread_name: String,
+}
+
+async fn fetch_thread_stop(thread: &dyn Thread) -> u32 {
+    match thread.fetch_setting().await {
+        Ok(setting) => setting.thread_stop,
+        Err(e) => {
+            warn!("Failed to fetch board setting: {}", e);
+            DEFAULT_THREAD_STOP
+        }
+    }
+}
+
+fn emit<S: serde::Serialize + Clone>(app_handle: &AppHandle, label: &str, event: &str, payload: S) {
+    if let Err(e) = app_handle.emit_to(label, event, payload) {
+        warn!("Failed to emit {}: {}", event, e);
+    }
+}
+
+/// Tells the frontend that the window now follows another thread.
+pub fn emit_thread_changed(
+    app_handle: &AppHandle,
+    label: &str,
+    thread_url: &Url,
+    charset: &str,
+    thread_name: String,
+) {
+    let payload = ThreadChangedPayload {
+        thread_url: thread_url.to_string(),
+        charset: charset.to_owned(),
+        thread_name,
+    };
+    emit(app_handle, label, "thread-changed", payload);
+}
+
+fn rebind_thread(app_handle: &AppHandle, label: &str, thread_url: &Url, charset: &str) {
+    let state = state(app_handle);
+    let mut state = state.lock().unwrap();
+    if let Some(state) = state.try_window_state_mut(label) {
+        state.thread = Some(ThreadBinding {
+            thread_url: thread_url.to_string(),
+            charset: charset.to_owned(),
+        });
+    }
+}
+
+/// Posts in the dat cache are replayed before polling for the rest.
+fn load_cached(thread: &dyn Thread, charset: &str) -> (Option<Vec<Post>>, ReadCursor) {
+    match thread.load_cached(charset) {
+        Some((posts, cursor)) => (Some(posts).filter(|x| !x.is_empty()), cursor),
+        None => (None, ReadCursor::default()),
+    }
+}
+
+async fn run(app_handle: AppHandle, label: String, mut thread_url: Url, charset: String) {
+    let mut thread = match bbs::new(&thread_url) {
+        Ok(thread) => thread,
+        Err(e) => {
+            warn!("Failed to start thread polling: {}", e);
+            return;
+        }
+    };
+    let mut thread_stop = fetch_thread_stop(thread.as_ref()).await;
+    let (mut cached, mut cursor) = load_cached(thread.as_ref(), &charset);
+    let mut reply_tree = ReplyTree::default();
+    let mut ng_chained = HashSet::new();
+    let mut interval = MIN_INTERVAL;
+    while app_handle.get_webview_window(&label).is_some() {
+        let from_cache = cached.is_some();
+        let fetched = match cached.take() {
+            Some(posts) => Ok(Some(posts)),
+            None => thread.fetch_new_posts(&charset, &mut cursor).await,
+        };
+        let ended = match fetched {
+            Ok(Some(posts)) => {
+                interval = MIN_INTERVAL;
+                let canonical_url = thread.thread_url(thread.key());
+                let post_history = post_history(&app_handle);
+                if !from_cache {
+                    // Cached posts were read before anything awaiting a number was posted.
+                    post_history.resolve_post_numbers(canonical_url.as_str(), &posts);
+                }
+                let posts = ng_filter(&app_handle).apply(posts, &mut ng_chained);
+                let own_numbers = post_history.own_post_numbers(canonical_url.as_str());
+                let replies: Vec<_> = find_replies(&posts, &own_numbers)
+                    .into_iter()
+                    .cloned()
+                    .collect();
+                if !replies.is_empty() {
+                    let payload = ThreadRepliesPayload {
+                        thread_url: thread_url.to_string(),
+                        posts: replies,
+                    };
+                    emit(&app_handle, &label, "thread-replies", payload);
+                }
+                let replied_by = reply_tree
+                    .add_posts(&posts)
+                    .into_iter()
+                    .map(|x| (x, reply_tree.replied_by(x).to_vec()))
+                    .collect();
+                let payload = ThreadPostsPayload {
+                    thread_url: thread_url.to_string(),
+                    posts,
+                    replied_by,
+                };
+                emit(&app_handle, &label, "thread-posts", payload);
+                cursor.last_number >= thread_stop
+            }
+            Ok(None) => {
+                interval = (interval * 2).min(MAX_INTERVAL);
+                cursor.last_number >= thread_stop
+            }
+            Err(e) if e.is::<ThreadNotFound>() => {
+                if cursor.title.is_none() {
+                    // Gone before it was ever read; there is no title to find the next thread by.
+                    info!("Thread not found: {}", thread_url);
+                    return;
+                }
+                interval = MAX_INTERVAL;
+                true
+            }
+            Err(e) => {
+                log::trace!("{:?}", e);
+                warn!("Failed to fetch thread posts: {}", e);
+                interval = (interval * 2).min(MAX_INTERVAL);
+                false
+            }
+        };
+        if let (true, Some(title)) = (ended, &cursor.title) {
+            interval = MAX_INTERVAL;
+            match bbs::find_next_thread(thread.as_ref(), title).await {
+                Ok(Some((next_url, next_title))) => match bbs::new(&next_url) {
+                    Ok(next) => {
+                        info!("Next thread: {}", next_url);
+                        thread = next;
+                        thread_url = next_url;
+                        thread_stop = fetch_thread_stop(thread.as_ref()).await;
+                        (cached, cursor) = load_cached(thread.as_ref(), &charset);
+                        reply_tree = ReplyTree::default();
+                        ng_chained.clear();
+                        interval = MIN_INTERVAL;
+                        rebind_thread(&app_handle, &label, &thread_url, &charset);
+                        emit_thread_changed(&app_handle, &label, &thread_url, &charset, next_title);
+                        continue;
+                    }
+                    Err(e) => warn!("Failed to open next thread: {}", e),
+                },
+                Ok(None) => {}
+                Err(e) => warn!("Failed to find next thread: {}", e),
+            }
+        }
+        if !from_cache {
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+pub struct ThreadPoller {
+    task: JoinHandle<()>,
+}
+
+impl ThreadPoller {
+    pub fn start(app_handle: AppHandle, label: String, thread_url: Url, charset: String) -> Self {
+        let task = tauri::async_runtime::spawn(run(app_handle, label, thread_url, charset));
+        Self { task }
+    }
+}
+
+/// Resolves a channel's contact URL to its thread, or to the latest thread
+/// for a board URL. Returns the thread URL, charset and title.
+pub async fn resolve_contact_thread(contact_url: &str) -> Option<(Url, String, String)> {
+    let bbs_url = bbs::parse_bbs_url(Url::parse(contact_url).ok()?).ok()?;
+    match bbs::fetch_thread_url_encoding_name(&bbs_url).await {
+        Ok(x) => Some(x),
+        Err(e) => {
+            warn!("Failed to resolve contact URL: {}", e);
+            None
+        }
+    }
+}
+
+/// Starts polling `thread_url` for the window, replacing any previous thread.
+pub fn bind_thread(app_handle: &AppHandle, label: &str, thread_url: Url, charset: String) {
+    let poller = ThreadPoller::start(
+        app_handle.clone(),
+        label.to_owned(),
+        thread_url.clone(),
+        charset.clone(),
+    );
+    let state = state(app_handle);
+    let mut state = state.lock().unwrap();
+    if let Some(state) = state.try_window_state_mut(label) {
+        state.thread = Some(ThreadBinding {
+            thread_url: thread_url.to_string(),
+            charset,
+        });
+        state.thread_poller = Some(poller);
+    }
+}
+
+impl Drop for ThreadPoller {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
@@ -1,8 +1,69 @@
 pub mod resizer;
 
-use tauri::{PhysicalSize, Window};
+use log::warn;
+use tauri::{AppHandle, PhysicalSize, WebviewUrl, WebviewWindow, WebviewWindowBuilder, Window};
+use url::Url;
+use uuid::Uuid;
 
-use super::state::WindowState;
+use crate::peercast::{parse_channel_url, ChannelDetails, PeerCastClient};
+
+use super::{
+    settings::settings,
+    state::{state, StreamInfo, WindowState},
+};
+
+/// Rewrites a PeerCast channel URL, e.g. `peercast://pls/<ID>` given on the
+/// command line or by the OS, into the local node's stream.
+pub fn resolve_stream_info(app_handle: &AppHandle, mut stream_info: StreamInfo) -> StreamInfo {
+    let channel = stream_info
+        .url
+        .as_deref()
+        .and_then(|x| Url::parse(x).ok())
+        .and_then(|x| parse_channel_url(&x));
+    if let Some(channel) = channel {
+        let node = settings(app_handle).get().peercast_node;
+        match channel.stream_url(&node) {
+            Ok(url) => stream_info.url = Some(url),
+            Err(e) => warn!("{}", e),
+        }
+    }
+    stream_info
+}
+
+/// Asks the local node for the name, contact URL and so on of the channel
+/// being played. `None` if the stream isn't a channel or the node has no API.
+pub async fn fetch_channel_details(
+    app_handle: &AppHandle,
+    stream_info: &StreamInfo,
+) -> Option<ChannelDetails> {
+    let channel = parse_channel_url(&Url::parse(stream_info.url.as_deref()?).ok()?)?;
+    let node = settings(app_handle).get().peercast_node;
+    match PeerCastClient::new(&node)
+        .get_channel_info(&channel.id)
+        .await
+    {
+        Ok(x) => Some(x.info),
+        Err(e) => {
+            warn!("getChannelInfo failed: {:?}", e);
+            None
+        }
+    }
+}
+
+/// Opens another player window. Every URL handed over by the OS goes through here.
+pub fn open_player_window(app_handle: &AppHandle, stream_info: StreamInfo) -> WebviewWindow {
+    let webview_url = WebviewUrl::App("index.html".into());
+    let window = WebviewWindowBuilder::new(app_handle, Uuid::new_v4().to_string(), webview_url)
+        .title("pcoplayer")
+        .build()
+        .unwrap();
+    let stream_info = resolve_stream_info(app_handle, stream_info);
+    state(app_handle)
+        .lock()
+        .unwrap()
+        .init_window(&window, stream_info);
+    window
+}
 
 pub fn window_frame_size(
     window: &Window,
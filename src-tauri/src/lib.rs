@@ -1,14 +1,20 @@
 mod bbs;
 mod core;
+mod peercast;
+mod yp;
 
 use core::commands;
 
 use clap::Parser;
-use tauri::Manager;
+use tauri::{Manager, WindowEvent};
 
 use crate::core::{
     platform,
-    state::{init_state, StreamInfo},
+    post_history::init_post_history,
+    post_queue::init_post_queue,
+    settings::init_settings,
+    state::{init_state, state, StreamInfo},
+    window::resolve_stream_info,
 };
 
 #[derive(Clone, Debug, clap::Parser, serde::Serialize)]
@@ -23,21 +29,65 @@ struct Args {
 pub fn run() {
     let args = Args::parse();
 
-    tauri::Builder::default()
+    let builder = tauri::Builder::default();
+    // Another launch, e.g. for a `peercast://` link, opens a window here instead.
+    #[cfg(any(target_os = "windows", target_os = "linux"))]
+    let builder = builder.plugin(tauri_plugin_single_instance::init(|app, argv, _cwd| {
+        match Args::try_parse_from(argv) {
+            Ok(args) => {
+                core::window::open_player_window(app, args.stream_info);
+            }
+            Err(e) => log::warn!("Invalid arguments: {}", e),
+        }
+    }));
+    builder
         .plugin(tauri_plugin_log::Builder::new().build())
         .plugin(tauri_plugin_shell::init())
         .invoke_handler(tauri::generate_handler![
             commands::initialize,
             commands::resolve_url,
+            commands::list_threads,
+            commands::fetch_posts,
             commands::post,
+            commands::find_unencodable_chars,
+            commands::enqueue_post,
+            commands::get_post_queue,
+            commands::retry_post,
+            commands::delete_post_draft,
+            commands::query_post_history,
+            commands::delete_post_history,
+            commands::get_settings,
+            commands::set_settings,
+            commands::list_yp_channels,
+            commands::open_yp_channel,
+            commands::start_thread_polling,
+            commands::stop_thread_polling,
+            commands::start_stall_detection,
+            commands::report_stream_data,
+            commands::report_buffering,
+            commands::get_channel_status,
+            commands::get_channel_relay_tree,
+            commands::stop_channel,
             commands::resize_video,
             commands::resize_interface,
         ])
         .setup(move |app| {
             platform::setup(app.app_handle().to_owned());
-            init_state(app, args.stream_info);
+            bbs::init(&app.path().app_data_dir()?, &app.path().app_cache_dir()?);
+            init_post_queue(app)?;
+            init_post_history(app)?;
+            init_settings(app)?;
+            let stream_info = resolve_stream_info(app.handle(), args.stream_info);
+            init_state(app, stream_info);
             Ok(())
         })
+        .on_window_event(|window, event| {
+            if let WindowEvent::Destroyed = event {
+                let state = state(window.app_handle());
+                let mut state = state.lock().unwrap();
+                state.remove_window(window.label());
+            }
+        })
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
@@ -0,0 +1,10 @@
+mod channel_url;
+mod rpc;
+
+pub use self::channel_url::{parse_channel_url, parse_node, ChannelUrl};
+pub use self::rpc::{ChannelDetails, ChannelStatus, PeerCastClient, RelayNode};
+
+pub const DEFAULT_NODE: &str = "localhost:7144";
+
+/// Schemes registered with the OS so that YP pages can open channels.
+pub const URL_SCHEMES: &[&str] = &["peercast", "pcp"];
@@ -0,0 +1,197 @@
+use anyhow::{bail, Result};
+use regex::Regex;
+use url::Url;
+
+/// A channel referred to by a PeerCast URL.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChannelUrl {
+    /// 32 uppercase hex digits.
+    pub id: String,
+    /// `host:port` of a node that relays the channel.
+    pub tip: Option<String>,
+    /// Container requested by `/stream/<ID>.<ext>`.
+    pub ext: Option<String>,
+}
+
+/// Parses the `host:port` of a PeerCast node into its root URL.
+pub fn parse_node(node: &str) -> Result<Url> {
+    let url = match Url::parse(&format!("http://{}/", node)) {
+        Ok(url) => url,
+        Err(e) => bail!("Invalid PeerCast node {:?}: {}", node, e),
+    };
+    let is_host_port = url.host_str().is_some_and(|x| !x.is_empty())
+        && url.username().is_empty()
+        && url.path() == "/"
+        && url.query().is_none()
+        && url.fragment().is_none();
+    if !is_host_port {
+        bail!("Invalid PeerCast node {:?}", node);
+    }
+    Ok(url)
+}
+
+impl ChannelUrl {
+    /// The stream served by the PeerCast node at `node` (`host:port`).
+    pub fn stream_url(&self, node: &str) -> Result<String> {
+        let ext = self.ext.as_deref().unwrap_or("flv");
+        let mut url = parse_node(node)?.join(&format!("stream/{}.{}", self.id, ext))?;
+        // A tip pointing at the node itself comes from its own stream URL.
+        if let Some(tip) = self.tip.as_deref().filter(|&x| x != node) {
+            url.query_pairs_mut().append_pair("tip", tip);
+        }
+        Ok(url.to_string())
+    }
+}
+
+fn parse_id(text: &str) -> Option<String> {
+    Regex::new(r"^[0-9A-Fa-f]{32}$")
+        .unwrap()
+        .is_match(text)
+        .then(|| text.to_ascii_uppercase())
+}
+
+fn query_tip(url: &Url) -> Option<String> {
+    url.query_pairs()
+        .find(|(name, _)| name == "tip")
+        .map(|(_, value)| value.into_owned())
+        .filter(|x| !x.is_empty())
+}
+
+fn host_port(url: &Url) -> Option<String> {
+    let host = url.host_str()?;
+    Some(match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_owned(),
+    })
+}
+
+/// Parses `http://<node>/stream/<ID>.flv`, `http://<node>/pls/<ID>?tip=host:port`,
+/// `peercast://pls/<ID>` and `pcp://host:port/<ID>`. Without `tip`, the node
+/// of an HTTP URL is the tip, since it relays the channel.
+pub fn parse_channel_url(url: &Url) -> Option<ChannelUrl> {
+    match url.scheme() {
+        "http" | "https" => {
+            let c = Regex::new(r"^/(stream|pls)/([0-9A-Fa-f]{32})(?:\.([A-Za-z0-9]+))?$")
+                .unwrap()
+                .captures(url.path())?;
+            let ext = match &c[1] {
+                "stream" => c.get(3).map(|x| x.as_str().to_ascii_lowercase()),
+                _ => None,
+            };
+            Some(ChannelUrl {
+                id: c[2].to_ascii_uppercase(),
+                tip: query_tip(url).or_else(|| host_port(url)),
+                ext,
+            })
+        }
+        "peercast" => {
+            // `peercast://pls/<ID>`: "pls" is the host; `peercast://pcp://...` is also seen.
+            let rest = url.as_str().strip_prefix("peercast://")?;
+            // The URL parser turns `peercast://pcp://` into `peercast://pcp//`.
+            if let Some(pcp) = rest.strip_prefix("pcp") {
+                let pcp = format!("pcp://{}", pcp.trim_start_matches([':', '/']));
+                return parse_channel_url(&Url::parse(&pcp).ok()?);
+            }
+            let (_, rest) = rest.split_once('/')?;
+            let id = rest.split(['?', '#', '.']).next()?;
+            Some(ChannelUrl {
+                id: parse_id(id)?,
+                tip: query_tip(url),
+                ext: None,
+            })
+        }
+        "pcp" => {
+            let id = url.path().trim_matches('/');
+            match parse_id(id) {
+                Some(id) => Some(ChannelUrl {
+                    id,
+                    tip: query_tip(url).or_else(|| host_port(url)),
+                    ext: None,
+                }),
+                // `pcp://<ID>` without a tracker.
+                None => Some(ChannelUrl {
+                    id: parse_id(url.host_str()?)?,
+                    tip: query_tip(url),
+                    ext: None,
+                }),
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "0123456789ABCDEF0123456789ABCDEF";
+
+    fn parse(url: &str) -> Option<ChannelUrl> {
+        parse_channel_url(&Url::parse(url).unwrap())
+    }
+
+    fn channel(tip: Option<&str>, ext: Option<&str>) -> Option<ChannelUrl> {
+        Some(ChannelUrl {
+            id: ID.to_owned(),
+            tip: tip.map(|x| x.to_owned()),
+            ext: ext.map(|x| x.to_owned()),
+        })
+    }
+
+    #[test]
+    fn parses_channel_urls() {
+        let lower = ID.to_ascii_lowercase();
+        assert_eq!(
+            parse(&format!("http://localhost:7144/stream/{}.FLV", lower)),
+            channel(Some("localhost:7144"), Some("flv"))
+        );
+        assert_eq!(
+            parse(&format!(
+                "http://localhost:7144/pls/{}?tip=1.2.3.4:7144",
+                ID
+            )),
+            channel(Some("1.2.3.4:7144"), None)
+        );
+        assert_eq!(
+            parse(&format!("peercast://pls/{}?tip=1.2.3.4:7144", ID)),
+            channel(Some("1.2.3.4:7144"), None)
+        );
+        assert_eq!(
+            parse(&format!("peercast://pcp://1.2.3.4:7144/{}", ID)),
+            channel(Some("1.2.3.4:7144"), None)
+        );
+        assert_eq!(parse(&format!("pcp://{}", ID)), channel(None, None));
+        assert_eq!(parse("http://localhost:7144/stream/0123"), None);
+        assert_eq!(parse(&format!("ftp://localhost/stream/{}", ID)), None);
+    }
+
+    #[test]
+    fn builds_stream_urls() {
+        let url = channel(Some("1.2.3.4:7144"), Some("mkv")).unwrap();
+        assert_eq!(
+            url.stream_url("localhost:7144").unwrap(),
+            format!("http://localhost:7144/stream/{}.mkv?tip=1.2.3.4%3A7144", ID)
+        );
+        let url = channel(Some("localhost:7144"), None).unwrap();
+        assert_eq!(
+            url.stream_url("localhost:7144").unwrap(),
+            format!("http://localhost:7144/stream/{}.flv", ID)
+        );
+        assert!(url.stream_url("localhost:7144/x").is_err());
+    }
+
+    #[test]
+    fn validates_nodes() {
+        assert!(parse_node("localhost:7144").is_ok());
+        assert!(parse_node("[::1]:7144").is_ok());
+        for node in [
+            "",
+            "user@host:7144",
+            "host:7144/path",
+            "host:7144?a",
+            "host:port",
+        ] {
+            assert!(parse_node(node).is_err(), "{}", node);
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use std::{fmt, time::Duration};
+
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ChannelDetails {
+    pub name: String,
+    /// Contact URL.
+    pub url: String,
+    pub genre: String,
+    pub desc: String,
+    pub comment: String,
+    pub bitrate: u32,
+    pub content_type: String,
+    pub mime_type: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ChannelTrack {
+    pub name: String,
+    pub genre: String,
+    pub album: String,
+    pub creator: String,
+    pub url: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ChannelInfo {
+    pub info: ChannelDetails,
+    pub track: ChannelTrack,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ChannelStatus {
+    /// e.g. "Idle", "Searching", "Connecting", "Receiving", "Error"
+    pub status: String,
+    pub source: String,
+    /// Seconds.
+    pub uptime: u64,
+    pub local_relays: u32,
+    pub local_directs: u32,
+    pub total_relays: u32,
+    pub total_directs: u32,
+    pub is_broadcasting: bool,
+    pub is_relay_full: bool,
+    pub is_direct_full: bool,
+    pub is_receiving: bool,
+}
+
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RelayNode {
+    pub session_id: String,
+    pub address: String,
+    pub port: u16,
+    pub is_firewalled: bool,
+    pub local_relays: u32,
+    pub local_directs: u32,
+    pub is_tracker: bool,
+    pub is_relay_full: bool,
+    pub is_direct_full: bool,
+    pub is_receiving: bool,
+    pub is_control_full: bool,
+    pub version: Option<u32>,
+    pub children: Vec<RelayNode>,
+}
+
+/// An error object returned by the node.
+#[derive(Debug, serde::Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+#[derive(serde::Deserialize)]
+struct Response {
+    #[serde(default)]
+    result: Value,
+    error: Option<RpcError>,
+}
+
+/// The node is local; a slow answer means it is stuck.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Client of the JSON-RPC API at `/api/1` of PeerCastStation compatible nodes.
+pub struct PeerCastClient {
+    endpoint: String,
+    client: reqwest::Client,
+}
+
+impl PeerCastClient {
+    /// `node` is `host:port`.
+    pub fn new(node: &str) -> Self {
+        Self {
+            endpoint: format!("http://{}/api/1", node),
+            client: reqwest::Client::builder().timeout(TIMEOUT).build().unwrap(),
+        }
+    }
+
+    async fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T> {
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": params,
+        });
+        let resp: Response = self
+            .client
+            .post(&self.endpoint)
+            // Required by PeerCastStation to accept API calls.
+            .header("X-Requested-With", "XMLHttpRequest")
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if let Some(error) = resp.error {
+            return Err(error.into());
+        }
+        Ok(serde_json::from_value(resp.result)?)
+    }
+
+    pub async fn get_channel_info(&self, channel_id: &str) -> Result<ChannelInfo> {
+        self.call("getChannelInfo", json!({ "channelId": channel_id }))
+            .await
+    }
+
+    pub async fn get_channel_status(&self, channel_id: &str) -> Result<ChannelStatus> {
+        self.call("getChannelStatus", json!({ "channelId": channel_id }))
+            .await
+    }
+
+    pub async fn get_channel_relay_tree(&self, channel_id: &str) -> Result<Vec<RelayNode>> {
+        self.call("getChannelRelayTree", json!({ "channelId": channel_id }))
+            .await
+    }
+
+    /// Reconnects the channel to another source.
+    pub async fn bump_channel(&self, channel_id: &str) -> Result<()> {
+        self.call::<Value>("bumpChannel", json!({ "channelId": channel_id }))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn stop_channel(&self, channel_id: &str) -> Result<()> {
+        self.call::<Value>("stopChannel", json!({ "channelId": channel_id }))
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        task::JoinHandle,
+    };
+
+    use super::*;
+
+    fn is_complete(request: &[u8]) -> bool {
+        let text = String::from_utf8_lossy(request);
+        let Some((head, body)) = text.split_once("\r\n\r\n") else {
+            return false;
+        };
+        let content_length = head
+            .lines()
+            .filter_map(|x| x.split_once(':'))
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+            .and_then(|(_, value)| value.trim().parse().ok())
+            .unwrap_or(0);
+        body.len() >= content_length
+    }
+
+    /// Answers a single request with `status` and `body`, and returns the
+    /// node address and the received request.
+    async fn serve_once(status: &str, body: &str) -> (String, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let node = listener.local_addr().unwrap().to_string();
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 4096];
+            while !is_complete(&request) {
+                let n = stream.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+            }
+            stream.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8(request).unwrap()
+        });
+        (node, server)
+    }
+
+    #[tokio::test]
+    async fn returns_result() {
+        let (node, server) = serve_once(
+            "200 OK",
+            r#"{"jsonrpc":"2.0","id":1,"result":{"info":{"name":"ch","url":"http://example.com/","genre":"game","desc":"desc","comment":"hi","bitrate":800},"track":{"name":"song"}}}"#,
+        )
+        .await;
+        let info = PeerCastClient::new(&node)
+            .get_channel_info("0123456789ABCDEF0123456789ABCDEF")
+            .await
+            .unwrap();
+        assert_eq!(info.info.name, "ch");
+        assert_eq!(info.info.url, "http://example.com/");
+        assert_eq!(info.info.bitrate, 800);
+        assert_eq!(info.track.name, "song");
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /api/1 "));
+        assert!(request
+            .to_ascii_lowercase()
+            .contains("x-requested-with: xmlhttprequest"));
+        assert!(request.contains(r#""method":"getChannelInfo""#));
+        assert!(request.contains(r#""channelId":"0123456789ABCDEF0123456789ABCDEF""#));
+    }
+
+    #[tokio::test]
+    async fn returns_error_object() {
+        let (node, _server) = serve_once(
+            "200 OK",
+            r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32602,"message":"Channel not found"}}"#,
+        )
+        .await;
+        let err = PeerCastClient::new(&node)
+            .bump_channel("0123456789ABCDEF0123456789ABCDEF")
+            .await
+            .unwrap_err();
+        let err = err.downcast::<RpcError>().unwrap();
+        assert_eq!(err.code, -32602);
+        assert_eq!(err.message, "Channel not found");
+    }
+
+    #[tokio::test]
+    async fn fails_on_http_error() {
+        let (node, _server) = serve_once("403 Forbidden", "").await;
+        let err = PeerCastClient::new(&node)
+            .get_channel_status("0123456789ABCDEF0123456789ABCDEF")
+            .await
+            .unwrap_err();
+        let err = err.downcast::<reqwest::Error>().unwrap();
+        assert_eq!(err.status(), Some(reqwest::StatusCode::FORBIDDEN));
+    }
+}
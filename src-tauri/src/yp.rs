@@ -0,0 +1,155 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use encoding_rs::UTF_8;
+use futures::future::join_all;
+use log::warn;
+use regex::Regex;
+
+use crate::{
+    bbs::{self, decode_entities},
+    peercast::ChannelUrl,
+};
+
+/// `index.txt` of the YPs listed when nothing is configured.
+pub const DEFAULT_YP_URLS: &[&str] = &[
+    "http://bayonet.ddo.jp/sp/index.txt",
+    "http://temp.orz.hm/yp/index.txt",
+];
+
+/// A line of a YP's `index.txt`.
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct YpChannel {
+    pub name: String,
+    /// 32 uppercase hex digits.
+    pub id: String,
+    /// `host:port` of the broadcasting node.
+    pub tip: String,
+    pub contact_url: String,
+    pub genre: String,
+    pub description: String,
+    /// -1 when hidden by the broadcaster.
+    pub listeners: i32,
+    pub relays: i32,
+    /// kbps.
+    pub bitrate: u32,
+    /// e.g. `FLV`, `WMV`, `MKV`.
+    pub content_type: String,
+    pub track_artist: String,
+    pub track_album: String,
+    pub track_title: String,
+    pub track_url: String,
+    /// Minutes.
+    pub uptime: u32,
+    pub comment: String,
+    /// `index.txt` the channel was listed in.
+    pub yp_url: String,
+}
+
+impl YpChannel {
+    pub fn channel_url(&self) -> ChannelUrl {
+        ChannelUrl {
+            id: self.id.clone(),
+            tip: Some(self.tip.clone()).filter(|x| !x.is_empty()),
+            ext: Some(self.content_type.to_ascii_lowercase()).filter(|x| !x.is_empty()),
+        }
+    }
+}
+
+/// Parses `h:mm`.
+fn parse_uptime(text: &str) -> u32 {
+    let Some((hours, minutes)) = text.split_once(':') else {
+        return 0;
+    };
+    let hours: u32 = hours.trim().parse().unwrap_or(0);
+    let minutes: u32 = minutes.trim().parse().unwrap_or(0);
+    hours * 60 + minutes
+}
+
+fn parse_line(line: &str, yp_url: &str) -> Option<YpChannel> {
+    let fields: Vec<_> = line.split("<>").map(decode_entities).collect();
+    if fields.len() < 18 {
+        return None;
+    }
+    let field = |i: usize| fields[i].clone();
+    let number = |i: usize| fields[i].trim().parse().unwrap_or(0);
+    Some(YpChannel {
+        name: field(0),
+        id: fields[1].to_ascii_uppercase(),
+        tip: field(2),
+        contact_url: field(3),
+        genre: field(4),
+        description: field(5),
+        listeners: number(6),
+        relays: number(7),
+        bitrate: fields[8].trim().parse().unwrap_or(0),
+        content_type: field(9),
+        track_artist: field(10),
+        track_album: field(11),
+        track_title: field(12),
+        track_url: field(13),
+        uptime: parse_uptime(&fields[15]),
+        comment: field(17),
+        yp_url: yp_url.to_owned(),
+    })
+}
+
+/// Parses an `index.txt`. Notices from the YP, which have a zero channel ID,
+/// are left out.
+pub fn parse_index_txt(text: &str, yp_url: &str) -> Vec<YpChannel> {
+    let id = Regex::new(r"^[0-9A-F]{32}$").unwrap();
+    text.lines()
+        .filter_map(|line| parse_line(line, yp_url))
+        .filter(|x| id.is_match(&x.id) && x.id.bytes().any(|b| b != b'0'))
+        .collect()
+}
+
+pub async fn fetch_index_txt(yp_url: &str) -> Result<Vec<YpChannel>> {
+    let text = bbs::fetch_text(yp_url, UTF_8).await?;
+    Ok(parse_index_txt(&text, yp_url))
+}
+
+/// Fetches every YP and lists their channels in one directory. A channel
+/// listed on several YPs appears once, as listed on the first of them.
+pub async fn fetch_channels(yp_urls: &[String]) -> Vec<YpChannel> {
+    let results = join_all(yp_urls.iter().map(|x| fetch_index_txt(x))).await;
+    let mut ids = HashSet::new();
+    let mut channels = Vec::new();
+    for (yp_url, result) in yp_urls.iter().zip(results) {
+        match result {
+            Ok(list) => channels.extend(list.into_iter().filter(|x| ids.insert(x.id.clone()))),
+            Err(e) => warn!("Failed to fetch {}: {}", yp_url, e),
+        }
+    }
+    channels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YP_URL: &str = "http://yp.example.com/index.txt";
+
+    #[test]
+    fn parses_index_txt() {
+        let text = concat!(
+            "テスト &amp; 配信<>0123456789abcdef0123456789abcdef<>1.2.3.4:7144<>http://example.com/bbs/<>ゲーム<>説明<>12<>3<>1500<>FLV<>artist<>album<>title<>http://example.com/<>id<>1:05<>click<>コメント<>0\n",
+            "YPからのお知らせ<>00000000000000000000000000000000<><><><><>-9<>-9<>0<>RAW<><><><><><>0:00<><><>0\n",
+            "short<>line\n",
+        );
+        let channels = parse_index_txt(text, YP_URL);
+        assert_eq!(channels.len(), 1);
+        let channel = &channels[0];
+        assert_eq!(channel.name, "テスト & 配信");
+        assert_eq!(channel.id, "0123456789ABCDEF0123456789ABCDEF");
+        assert_eq!(channel.tip, "1.2.3.4:7144");
+        assert_eq!(channel.listeners, 12);
+        assert_eq!(channel.relays, 3);
+        assert_eq!(channel.bitrate, 1500);
+        assert_eq!(channel.uptime, 65);
+        assert_eq!(channel.comment, "コメント");
+        assert_eq!(channel.yp_url, YP_URL);
+        assert_eq!(channel.channel_url().ext.as_deref(), Some("flv"));
+    }
+}
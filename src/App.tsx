@@ -22,7 +22,7 @@ export default function App(props: {
   textareaRef: React.RefObject<HTMLTextAreaElement>;
   threadName: string;
   message?: {
-    intent: "success" | "error";
+    intent: "success" | "warning" | "error";
     text: string;
   } | null;
   onResizeVideo(videoWidth: number, videoHeight: number): void;
@@ -13,6 +13,11 @@ async function initPlayer(
     isLive: true,
     url,
   });
+  player.on(mpegts.Events.STATISTICS_INFO, (info: { speed?: number }) => {
+    if ((info.speed ?? 0) > 0) {
+      invoke("report_stream_data");
+    }
+  });
   player.attachMediaElement(video);
   player.load();
   return player;
@@ -43,9 +48,12 @@ export default function AppContaier(): JSX.Element {
   const [threadName, setThreadName] = useState<string>("");
   const [bbs, setBbs] = useState<{ url: string; charset: string } | null>(null);
   const [message, setMessage] = useState<{
-    intent: "success" | "error";
+    intent: "success" | "warning" | "error";
     text: string;
   } | null>(null);
+  const streamUrlRef = useRef<string | null>(null);
+  const playerRef = useRef<mpegts.Player | null>(null);
+  playerRef.current = player;
 
   useEffect(() => {
     const video = videoRef.current!;
@@ -58,10 +66,74 @@ export default function AppContaier(): JSX.Element {
         videoClientWidth: video.clientWidth,
         videoClientHeight: video.clientHeight,
       });
+      streamUrlRef.current = initialData.url;
+      await invoke("start_stall_detection", { url: initialData.url });
       setPlayer(await refreshPlayer(video, player, initialData.url));
     })();
   }, []);
 
+  useEffect(() => {
+    const video = videoRef.current!;
+    const onWaiting = () => invoke("report_buffering", { buffering: true });
+    const onPlaying = () => invoke("report_buffering", { buffering: false });
+    video.addEventListener("waiting", onWaiting);
+    video.addEventListener("playing", onPlaying);
+    return () => {
+      video.removeEventListener("waiting", onWaiting);
+      video.removeEventListener("playing", onPlaying);
+    };
+  }, []);
+
+  useEffect(() => {
+    const unlisten = getCurrentWindow().listen<{
+      status: "reconnecting" | "playing";
+    }>("stream-status", async (ev) => {
+      if (ev.payload.status === "playing") {
+        setMessage(null);
+        return;
+      }
+      setMessage({ intent: "warning", text: "再接続中" });
+      const url = streamUrlRef.current;
+      if (url != null) {
+        setPlayer(
+          await refreshPlayer(videoRef.current!, playerRef.current, url)
+        );
+      }
+    });
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
+
+  useEffect(() => {
+    const unlisten = getCurrentWindow().listen<{
+      threadUrl: string;
+      charset: string;
+      threadName: string;
+    }>("thread-changed", (ev) => {
+      setThreadName(ev.payload.threadName);
+      setBbs({ url: ev.payload.threadUrl, charset: ev.payload.charset });
+    });
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
+
+  useEffect(() => {
+    const unlisten = getCurrentWindow().listen<{
+      error: { message: string } | null;
+    }>("post-result", (ev) => {
+      if (ev.payload.error == null) {
+        setMessage({ intent: "success", text: "書き込みました" });
+      } else {
+        setMessage({ intent: "error", text: ev.payload.error.message });
+      }
+    });
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
+
   useEffect(() => {
     const handlePaste = async (e: ClipboardEvent) => {
       if (e.target === textareaRef.current) {
@@ -75,13 +147,19 @@ export default function AppContaier(): JSX.Element {
       if (result == null) {
         return;
       }
-      if (result.type === "stream") {
+      if (result.type === "stream" || result.type === "peerCastChannel") {
+        streamUrlRef.current = result.streamUrl;
+        await invoke("start_stall_detection", { url: result.streamUrl });
         setPlayer(
           await refreshPlayer(videoRef.current!, player, result.streamUrl)
         );
       } else if (result.type === "bbs") {
         setThreadName(result.threadName);
         setBbs({ url: result.threadUrl, charset: result.charset });
+        await invoke("start_thread_polling", {
+          url: result.threadUrl,
+          charset: result.charset,
+        });
       }
     };
     document.addEventListener("paste", handlePaste);
@@ -121,15 +199,9 @@ export default function AppContaier(): JSX.Element {
       onClickPost={async (comment: string) => {
         const url = bbs?.url;
         const charset = bbs?.charset;
-        const name = "";
-        const email = "sage";
         const msg = comment;
         try {
-          await invoke("post", { url, charset, name, email, msg });
-          setMessage({
-            intent: "success",
-            text: "書き込みました",
-          });
+          await invoke("enqueue_post", { url, charset, msg });
         } catch (e) {
           setMessage({
             intent: "error",
@@ -58,7 +58,7 @@ const useStyles = makeStyles({
 export default function Info(props: {
   threadName: string;
   message?: {
-    intent: "success" | "error";
+    intent: "success" | "warning" | "error";
     text: string;
   } | null;
   onResize(): void;